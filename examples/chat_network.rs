@@ -39,7 +39,7 @@ impl network::NetworkMessage for ChatMessage {
 struct ChatNodeData {
     name: String,
     message_log: RefCell<Vec<ChatMessage>>,
-    notification_sender: RefCell<Option<sync::mpsc::Sender<ChatMessage>>>,
+    notification_sender: RefCell<Option<sync::mpsc::UnboundedSender<ChatMessage>>>,
 }
 
 impl network::NodeData for ChatNodeData {}
@@ -51,7 +51,7 @@ impl network::NodeCallback<ChatMessage, ChatNodeData> for ChatNodeCallback {
     async fn handle_message(
         &self,
         node: &Rc<network::Node<ChatMessage, ChatNodeData>>,
-        source: network::ObjectId,
+        source: network::NodeId,
         message: ChatMessage,
     ) {
         let node_name = &node.get_data().name;
@@ -90,7 +90,8 @@ impl network::NodeCallback<ChatMessage, ChatNodeData> for ChatNodeCallback {
 
         // Notify any waiting listeners
         if let Some(sender) = node.get_data().notification_sender.borrow_mut().as_ref() {
-            sender.send(message);
+            // Nobody might be listening anymore
+            let _ = sender.send(message);
         }
     }
 
@@ -105,7 +106,7 @@ impl network::NodeCallback<ChatMessage, ChatNodeData> for ChatNodeCallback {
     fn peer_disconnected(
         &self,
         node: &network::Node<ChatMessage, ChatNodeData>,
-        peer: network::ObjectId,
+        peer: network::NodeId,
     ) {
         println!(
             "[{}] {} lost connection to peer {}",
@@ -122,8 +123,8 @@ struct ChatLinkCallback;
 impl network::LinkCallback<ChatMessage, ChatNodeData> for ChatLinkCallback {
     fn message_sent(
         &self,
-        source: &network::ObjectId,
-        destination: &network::ObjectId,
+        source: &network::NodeId,
+        destination: &network::NodeId,
         message: &ChatMessage,
     ) {
         println!(
@@ -147,7 +148,9 @@ fn create_chat_node(
         notification_sender: RefCell::new(None),
     };
 
-    network::Node::new(bandwidth, data, Box::new(ChatNodeCallback))
+    let node = network::Node::new(bandwidth, data, Box::new(ChatNodeCallback));
+    node.set_name(name);
+    node
 }
 
 fn main() {
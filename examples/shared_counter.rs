@@ -29,8 +29,7 @@ fn main() {
         let counter = Rc::new(sync::Mutex::new(0i32));
 
         // Create a channel for tasks to report their work
-        let (sender, receiver) = sync::mpsc::channel();
-        let sender = Rc::new(sender);
+        let (sender, receiver) = sync::mpsc::unbounded_channel();
 
         println!(
             "Starting simulation with shared counter at time {:?}",
@@ -70,36 +69,34 @@ fn main() {
                     } // Lock is automatically released here
 
                     // Notify completion of this iteration
-                    sender_clone.send(format!(
-                        "Task {} completed iteration {}",
-                        task_id, iteration
-                    ));
+                    sender_clone
+                        .send(format!(
+                            "Task {} completed iteration {}",
+                            task_id, iteration
+                        ))
+                        .expect("Receiver is gone");
                 }
 
                 // Notify task completion
-                sender_clone.send(format!("Task {} finished all work", task_id));
+                sender_clone
+                    .send(format!("Task {} finished all work", task_id))
+                    .expect("Receiver is gone");
             });
         }
 
-        // Drop the original sender reference so the receiver knows when all tasks are done
-        // Note: we need to drop the Rc, but since tasks still hold references,
-        // we'll use a different approach - count completed tasks instead
+        // Drop the original sender, so the receiver knows when all tasks are done
+        drop(sender);
 
         // Collect reports from all tasks
         let mut completed_tasks = 0;
         let mut total_iterations = 0;
-        const TOTAL_TASKS: usize = 5;
 
-        while completed_tasks < TOTAL_TASKS {
-            let messages = receiver.recv().await;
-
-            for message in messages {
-                println!("Report: {}", message);
-                if message.contains("finished all work") {
-                    completed_tasks += 1;
-                } else if message.contains("completed iteration") {
-                    total_iterations += 1;
-                }
+        while let Some(message) = receiver.recv().await {
+            println!("Report: {}", message);
+            if message.contains("finished all work") {
+                completed_tasks += 1;
+            } else if message.contains("completed iteration") {
+                total_iterations += 1;
             }
         }
 
@@ -0,0 +1,422 @@
+/// Detection of tasks that wait for each other in a cycle
+///
+/// The synchronization primitives in `asim::sync` report which task holds them and
+/// which tasks wait for them. From this, the runtime builds a wait-for graph between tasks.
+/// A cycle in this graph means none of the tasks involved can ever make progress.
+///
+/// For channels, the holder of an endpoint is the task that used it most recently.
+/// This is a heuristic, so reports involving channels can be false positives
+/// while the simulation is still making progress.
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::panic::Location;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Task;
+
+static NEXT_RESOURCE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies a synchronization primitive (or one end of a channel) in the wait-for graph
+#[derive(Clone, Copy)]
+pub(crate) struct Resource {
+    identifier: u64,
+    kind: &'static str,
+    created_at: &'static Location<'static>,
+}
+
+impl Resource {
+    #[track_caller]
+    pub fn new(kind: &'static str) -> Self {
+        Self {
+            identifier: NEXT_RESOURCE_ID.fetch_add(1, Ordering::Relaxed),
+            kind,
+            created_at: Location::caller(),
+        }
+    }
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{} created at {}", self.kind, self.created_at)
+    }
+}
+
+struct ResourceEntry {
+    resource: Resource,
+    holders: Vec<(u64, Weak<Task>)>,
+    waiters: Vec<(u64, Weak<Task>)>,
+}
+
+/// Which tasks hold and wait for which resources
+#[derive(Default)]
+pub(crate) struct WaitGraph {
+    resources: BTreeMap<u64, ResourceEntry>,
+}
+
+fn remove_task(tasks: &mut Vec<(u64, Weak<Task>)>, task: u64) {
+    if let Some(pos) = tasks.iter().position(|(id, _)| *id == task) {
+        tasks.swap_remove(pos);
+    }
+}
+
+impl WaitGraph {
+    fn entry(&mut self, resource: &Resource) -> &mut ResourceEntry {
+        self.resources
+            .entry(resource.identifier)
+            .or_insert_with(|| ResourceEntry {
+                resource: *resource,
+                holders: vec![],
+                waiters: vec![],
+            })
+    }
+
+    fn cleanup(&mut self, resource: &Resource) {
+        if let Some(entry) = self.resources.get(&resource.identifier) {
+            if entry.holders.is_empty() && entry.waiters.is_empty() {
+                self.resources.remove(&resource.identifier);
+            }
+        }
+    }
+
+    fn add_holder(&mut self, resource: &Resource, task: &Rc<Task>) {
+        self.entry(resource)
+            .holders
+            .push((task.get_identifier(), Rc::downgrade(task)));
+    }
+
+    fn remove_holder(&mut self, resource: &Resource, task: u64) {
+        if let Some(entry) = self.resources.get_mut(&resource.identifier) {
+            remove_task(&mut entry.holders, task);
+        }
+        self.cleanup(resource);
+    }
+
+    fn add_waiter(&mut self, resource: &Resource, task: &Rc<Task>) {
+        self.entry(resource)
+            .waiters
+            .push((task.get_identifier(), Rc::downgrade(task)));
+    }
+
+    fn remove_waiter(&mut self, resource: &Resource, task: u64) {
+        if let Some(entry) = self.resources.get_mut(&resource.identifier) {
+            remove_task(&mut entry.waiters, task);
+        }
+        self.cleanup(resource);
+    }
+
+    /// Find cycles in the wait-for graph
+    ///
+    /// Every task shows up in at most one of the reported deadlocks
+    pub fn find_deadlocks(&self) -> Vec<Deadlock> {
+        // Task -> (resource it waits for, task holding that resource)
+        let mut edges: BTreeMap<u64, Vec<(&ResourceEntry, u64)>> = BTreeMap::new();
+        let mut tasks = BTreeMap::new();
+
+        for entry in self.resources.values() {
+            for (waiter, waiter_task) in entry.waiters.iter() {
+                tasks.insert(*waiter, waiter_task);
+
+                for (holder, holder_task) in entry.holders.iter() {
+                    if holder != waiter {
+                        tasks.insert(*holder, holder_task);
+                        edges.entry(*waiter).or_default().push((entry, *holder));
+                    }
+                }
+            }
+        }
+
+        let task_name = |identifier: u64| match tasks.get(&identifier).and_then(|t| t.upgrade()) {
+            Some(task) => task.to_string(),
+            None => format!("Task #{identifier}"),
+        };
+
+        let mut deadlocks = vec![];
+        let mut done = std::collections::HashSet::new();
+
+        for start in edges.keys() {
+            if done.contains(start) {
+                continue;
+            }
+
+            // Depth-first search that remembers the current path
+            let mut path: Vec<(u64, usize)> = vec![(*start, 0)];
+            let mut on_path = std::collections::HashSet::from([*start]);
+
+            while let Some((task, next_edge)) = path.last_mut() {
+                let task = *task;
+                let Some((_, holder)) = edges.get(&task).and_then(|e| e.get(*next_edge)) else {
+                    done.insert(task);
+                    on_path.remove(&task);
+                    path.pop();
+                    continue;
+                };
+                *next_edge += 1;
+
+                if on_path.contains(holder) {
+                    let pos = path.iter().position(|(t, _)| t == holder).unwrap();
+                    let cycle = path[pos..]
+                        .iter()
+                        .map(|(task, next_edge)| {
+                            let (entry, _) = edges[task][next_edge - 1];
+                            DeadlockEdge {
+                                task: task_name(*task),
+                                resource: entry.resource.to_string(),
+                            }
+                        })
+                        .collect();
+
+                    deadlocks.push(Deadlock { cycle });
+
+                    for (task, _) in path.drain(..) {
+                        done.insert(task);
+                    }
+                    break;
+                } else if !done.contains(holder) {
+                    on_path.insert(*holder);
+                    path.push((*holder, 0));
+                }
+            }
+        }
+
+        deadlocks
+    }
+}
+
+/// One step in a deadlock: a task waiting for a resource
+/// that is held by the task of the next step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlockEdge {
+    pub task: String,
+    pub resource: String,
+}
+
+/// A set of tasks that wait for each other in a cycle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    /// The last task waits for a resource held by the first one
+    pub cycle: Vec<DeadlockEdge>,
+}
+
+impl std::fmt::Display for Deadlock {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "Deadlock between {} tasks:", self.cycle.len())?;
+
+        for (idx, edge) in self.cycle.iter().enumerate() {
+            let holder = &self.cycle[(idx + 1) % self.cycle.len()].task;
+            write!(
+                fmt,
+                "\n  {} waits for {} held by {holder}",
+                edge.task, edge.resource
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Find cycles of tasks in the current asim context that wait for each other
+///
+/// Note, this will panic if no asim context is active
+pub fn find_deadlocks() -> Vec<Deadlock> {
+    crate::get_runtime().find_deadlocks()
+}
+
+/// Run a function on the wait-for graph of the current context, if any
+fn with_graph<R>(func: impl FnOnce(&mut WaitGraph, Option<Rc<Task>>) -> R) -> Option<R> {
+    crate::CONTEXT.with(|hdl| {
+        let hdl = hdl.borrow();
+        let hdl = hdl.as_ref()?;
+        let mut graph = hdl.get_wait_graph().borrow_mut();
+        Some(func(&mut graph, hdl.current_task()))
+    })
+}
+
+/// The current task now holds the resource
+///
+/// Returns the identifier of the holding task, which has to be passed to [`released`]
+pub(crate) fn acquired(resource: &Resource) -> Option<u64> {
+    with_graph(|graph, task| {
+        let task = task?;
+        graph.add_holder(resource, &task);
+        Some(task.get_identifier())
+    })
+    .flatten()
+}
+
+pub(crate) fn released(resource: &Resource, task: Option<u64>) {
+    if let Some(task) = task {
+        with_graph(|graph, _| graph.remove_holder(resource, task));
+    }
+}
+
+/// Marks the current task as waiting for a resource until dropped
+pub(crate) struct Waiting {
+    resource: Resource,
+    task: Option<u64>,
+}
+
+impl Waiting {
+    pub fn new(resource: &Resource) -> Self {
+        let task = with_graph(|graph, task| {
+            let task = task?;
+            graph.add_waiter(resource, &task);
+            Some(task.get_identifier())
+        })
+        .flatten();
+
+        Self {
+            resource: *resource,
+            task,
+        }
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if let Some(task) = self.task {
+            with_graph(|graph, _| graph.remove_waiter(&self.resource, task));
+        }
+    }
+}
+
+/// Keeps track of the task that most recently used a channel endpoint
+pub(crate) struct EndpointUser {
+    resource: Resource,
+    task: Cell<Option<u64>>,
+}
+
+impl EndpointUser {
+    pub fn new(resource: &Resource) -> Self {
+        Self {
+            resource: *resource,
+            task: Cell::new(acquired(resource)),
+        }
+    }
+
+    /// Make the current task the holder of this endpoint
+    pub fn touch(&self) {
+        let Some(current) = crate::current_task() else {
+            return;
+        };
+
+        if self.task.get() != Some(current.get_identifier()) {
+            released(&self.resource, self.task.get());
+            self.task.set(acquired(&self.resource));
+        }
+    }
+}
+
+impl Drop for EndpointUser {
+    fn drop(&mut self) {
+        released(&self.resource, self.task.get());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::sync::{mpsc, Mutex};
+    use crate::time::Duration;
+
+    #[test]
+    fn lock_order_inversion() {
+        crate::Runtime::new().block_on(async {
+            let mutex1 = Rc::new(Mutex::new(()));
+            let mutex2 = Rc::new(Mutex::new(()));
+
+            for (name, first, second) in [
+                ("first", mutex1.clone(), mutex2.clone()),
+                ("second", mutex2.clone(), mutex1.clone()),
+            ] {
+                crate::spawn_named(name, async move {
+                    let _guard1 = first.lock().await;
+                    crate::time::sleep(Duration::from_millis(1)).await;
+                    let _guard2 = second.lock().await;
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(5)).await;
+
+            let deadlocks = super::find_deadlocks();
+            assert_eq!(deadlocks.len(), 1);
+
+            let cycle = &deadlocks[0].cycle;
+            assert_eq!(cycle.len(), 2);
+            assert_eq!(cycle[0].task, "Task \"first\"");
+            assert_eq!(cycle[1].task, "Task \"second\"");
+            assert!(cycle[0]
+                .resource
+                .starts_with("Mutex created at src/deadlock.rs"));
+        });
+    }
+
+    #[test]
+    fn moved_owned_guard() {
+        crate::Runtime::new().block_on(async {
+            let mutex1 = Rc::new(Mutex::new(0));
+            let mutex2 = Rc::new(Mutex::new(()));
+
+            {
+                let (mutex1, mutex2) = (mutex1.clone(), mutex2.clone());
+                crate::spawn_named("first", async move {
+                    let mut guard = mutex1.lock_owned().await;
+
+                    let mutex = mutex2.clone();
+                    crate::spawn_named("second", async move {
+                        crate::time::sleep(Duration::from_millis(3)).await;
+                        *guard += 1;
+                        let _guard = mutex.lock().await;
+                    });
+
+                    crate::time::sleep(Duration::from_millis(1)).await;
+                    let _guard = mutex2.lock().await;
+                });
+            }
+
+            crate::spawn_named("third", async move {
+                let _guard = mutex2.lock().await;
+                let _guard = mutex1.lock().await;
+            });
+
+            // The task that acquired the guard gave it away, so it is not to blame
+            crate::time::sleep(Duration::from_millis(2)).await;
+            assert!(super::find_deadlocks().is_empty());
+
+            // Once the other task uses the guard, it holds the lock
+            crate::time::sleep(Duration::from_millis(2)).await;
+            let deadlocks = super::find_deadlocks();
+            assert_eq!(deadlocks.len(), 1);
+
+            let tasks: Vec<_> = deadlocks[0].cycle.iter().map(|e| e.task.as_str()).collect();
+            assert_eq!(tasks, ["Task \"third\"", "Task \"second\""]);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "waits for mpsc::Receiver created at")]
+    fn stalled_on_channel() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(Mutex::new(()));
+            let (sender, receiver) = mpsc::channel(1);
+
+            let guard = mutex.lock().await;
+
+            {
+                let mutex = mutex.clone();
+                crate::spawn_named("receiver", async move {
+                    receiver.recv().await;
+                    let _guard = mutex.lock().await;
+                    receiver.recv().await;
+                });
+            }
+
+            // Eventually, the channel is full and the receiver waits for the lock we hold
+            for idx in 0..3 {
+                sender.send(idx).await.unwrap();
+            }
+            drop(guard);
+        });
+    }
+}
@@ -12,13 +12,25 @@ pub mod network;
 
 pub mod time;
 
+pub mod deadlock;
+
+pub mod resource;
+
+pub mod random;
+
 pub mod runtime;
 pub use runtime::Runtime;
 
 pub use asim_macros::test;
 
+mod parse;
+pub use parse::ParseError;
+
+mod scope;
+pub use scope::{scope, Scope, ScopeFut};
+
 mod task;
-pub use task::Task;
+pub use task::{Priority, Task};
 pub(crate) use task::{RcWrapper, TaskQueue};
 
 thread_local! {
@@ -38,6 +50,42 @@ pub fn spawn(future: impl Future<Output = ()> + 'static) {
     })
 }
 
+/// Spawn a new task with the specified priority in the current asim context
+///
+/// Among all tasks that are ready at the same simulated time,
+/// tasks with higher priority run first.
+///
+/// Note, this will panic if no asim context is active
+pub fn spawn_with_priority(priority: Priority, future: impl Future<Output = ()> + 'static) {
+    CONTEXT.with(|hdl| {
+        hdl.borrow()
+            .as_ref()
+            .expect("Not in an asim context!")
+            .spawn_with_priority(priority, future)
+    })
+}
+
+/// Spawn a new named task in the current asim context
+///
+/// The name is used when reporting problems with the task
+///
+/// Note, this will panic if no asim context is active
+pub fn spawn_named(name: impl Into<String>, future: impl Future<Output = ()> + 'static) {
+    CONTEXT.with(|hdl| {
+        hdl.borrow()
+            .as_ref()
+            .expect("Not in an asim context!")
+            .spawn_named(name, future)
+    })
+}
+
+/// The task that is currently running
+///
+/// Returns None if called outside of a task or outside of an asim context
+pub fn current_task() -> Option<std::rc::Rc<Task>> {
+    CONTEXT.with(|hdl| hdl.borrow().as_ref().and_then(|hdl| hdl.current_task()))
+}
+
 pub fn get_runtime() -> runtime::Handle {
     CONTEXT.with(|hdl| {
         hdl.borrow()
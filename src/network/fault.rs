@@ -0,0 +1,100 @@
+use std::cell::Cell;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::random::chance;
+use crate::time::Duration;
+
+/// How a link loses messages
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LossModel {
+    /// No message is lost
+    #[default]
+    None,
+    /// Every message is lost independently with the given probability
+    Bernoulli(f64),
+    /// Bursty loss
+    ///
+    /// The link switches between a good and a bad state before every message,
+    /// and each state has its own loss probability.
+    GilbertElliott {
+        good_to_bad: f64,
+        bad_to_good: f64,
+        loss_good: f64,
+        loss_bad: f64,
+    },
+}
+
+/// Faults a link injects into the messages it carries
+///
+/// All decisions are drawn from the seeded random number generator
+/// (see [`crate::random`]), so runs can be reproduced.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultModel {
+    pub loss: LossModel,
+    /// Probability that a message is delivered twice
+    pub duplication: f64,
+    /// Probability that a message is held back, so that later messages overtake it
+    pub reordering: f64,
+    /// How long a reordered message is held back
+    pub reordering_delay: Duration,
+}
+
+impl FaultModel {
+    pub(super) fn validate(&self) {
+        let mut probabilities = vec![self.duplication, self.reordering];
+
+        match self.loss {
+            LossModel::None => {}
+            LossModel::Bernoulli(loss) => probabilities.push(loss),
+            LossModel::GilbertElliott {
+                good_to_bad,
+                bad_to_good,
+                loss_good,
+                loss_bad,
+            } => probabilities.extend([good_to_bad, bad_to_good, loss_good, loss_bad]),
+        }
+
+        for probability in probabilities {
+            assert!(
+                (0.0..=1.0).contains(&probability),
+                "Invalid probability {probability} in fault model"
+            );
+        }
+    }
+}
+
+/// The state of a loss model for one direction of a link
+#[derive(Default)]
+pub(super) struct LossState {
+    /// Is the Gilbert-Elliott model in its bad state?
+    bad: Cell<bool>,
+}
+
+impl LossState {
+    /// Decide whether the next message is lost
+    pub fn is_lost(&self, model: &LossModel) -> bool {
+        match *model {
+            LossModel::None => false,
+            LossModel::Bernoulli(loss) => chance(loss),
+            LossModel::GilbertElliott {
+                good_to_bad,
+                bad_to_good,
+                loss_good,
+                loss_bad,
+            } => {
+                let bad = if self.bad.get() {
+                    !chance(bad_to_good)
+                } else {
+                    chance(good_to_bad)
+                };
+                self.bad.set(bad);
+
+                chance(if bad { loss_bad } else { loss_good })
+            }
+        }
+    }
+}
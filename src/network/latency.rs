@@ -0,0 +1,305 @@
+use std::path::Path;
+
+use crate::random::{Rng, RngCore};
+use crate::time::Duration;
+use crate::ParseError;
+
+/// Decides how long each message takes to travel across a link
+///
+/// Models draw from the random number generator they are given,
+/// which is the seeded generator of the runtime (see [`crate::random`]).
+pub trait LatencyModel {
+    /// Draw the latency of one message
+    ///
+    /// The runtime's generator is already in use while this is called, so implementations
+    /// must only draw from `rng`. Calling [`crate::random::with_rng`] here panics.
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration;
+
+    /// The average latency, e.g., to find the fastest route
+    fn mean(&self) -> Duration;
+}
+
+impl<T: LatencyModel + ?Sized> LatencyModel for Box<T> {
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        (**self).sample(rng)
+    }
+
+    fn mean(&self) -> Duration {
+        (**self).mean()
+    }
+}
+
+/// Converts a (possibly negative) number of nanoseconds into a duration
+pub(super) fn from_nanos_f64(nanos: f64) -> Duration {
+    // Casts saturate, so negative values become zero
+    Duration::from_nanos(nanos.round() as u64)
+}
+
+/// Draws from the standard normal distribution (using the Box-Muller transform)
+fn standard_normal(rng: &mut dyn RngCore) -> f64 {
+    // Avoid taking the logarithm of zero
+    let u1 = 1.0 - rng.random::<f64>();
+    let u2 = rng.random::<f64>();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Every message takes the same time
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantLatency(pub Duration);
+
+impl LatencyModel for ConstantLatency {
+    fn sample(&self, _rng: &mut dyn RngCore) -> Duration {
+        self.0
+    }
+
+    fn mean(&self) -> Duration {
+        self.0
+    }
+}
+
+/// Latencies are spread evenly between `min` and `max` (inclusive)
+#[derive(Debug, Clone, Copy)]
+pub struct UniformLatency {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl LatencyModel for UniformLatency {
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        let nanos = rng.random_range(self.min.as_nanos()..=self.max.as_nanos());
+        Duration::from_nanos(nanos)
+    }
+
+    fn mean(&self) -> Duration {
+        self.min + (self.max - self.min) / 2
+    }
+}
+
+/// Normally distributed latencies
+///
+/// Samples below zero are rounded up to zero.
+#[derive(Debug, Clone, Copy)]
+pub struct NormalLatency {
+    pub mean: Duration,
+    pub std_dev: Duration,
+}
+
+impl LatencyModel for NormalLatency {
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        let offset = standard_normal(rng) * self.std_dev.as_nanos() as f64;
+        from_nanos_f64(self.mean.as_nanos() as f64 + offset)
+    }
+
+    /// Note, this ignores that negative samples are rounded up
+    fn mean(&self) -> Duration {
+        self.mean
+    }
+}
+
+/// Log-normally distributed latencies, which have a long tail
+///
+/// `sigma` is the standard deviation of the latency's logarithm;
+/// the larger it is, the longer the tail.
+#[derive(Debug, Clone, Copy)]
+pub struct LogNormalLatency {
+    pub median: Duration,
+    pub sigma: f64,
+}
+
+impl LatencyModel for LogNormalLatency {
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        let factor = (self.sigma * standard_normal(rng)).exp();
+        from_nanos_f64(self.median.as_nanos() as f64 * factor)
+    }
+
+    fn mean(&self) -> Duration {
+        self.median * (self.sigma * self.sigma / 2.0).exp()
+    }
+}
+
+/// Pareto-distributed latencies, i.e., a heavy tail above `min`
+///
+/// The smaller `shape` is, the heavier the tail.
+#[derive(Debug, Clone, Copy)]
+pub struct ParetoLatency {
+    pub min: Duration,
+    pub shape: f64,
+}
+
+impl LatencyModel for ParetoLatency {
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        // Avoid dividing by zero
+        let uniform = 1.0 - rng.random::<f64>();
+        from_nanos_f64(self.min.as_nanos() as f64 / uniform.powf(1.0 / self.shape))
+    }
+
+    /// For a shape of one or less, the mean is infinite, so this returns the median instead
+    fn mean(&self) -> Duration {
+        if self.shape > 1.0 {
+            self.min * (self.shape / (self.shape - 1.0))
+        } else {
+            self.min * 2.0f64.powf(1.0 / self.shape)
+        }
+    }
+}
+
+/// Picks latencies from a set of measured samples
+#[derive(Debug, Clone)]
+pub struct EmpiricalLatency {
+    samples: Vec<Duration>,
+}
+
+impl EmpiricalLatency {
+    pub fn new(samples: Vec<Duration>) -> Self {
+        assert!(!samples.is_empty(), "Need at least one latency sample");
+        Self { samples }
+    }
+
+    /// Load samples from a CSV file
+    ///
+    /// See [`Self::from_csv_str`] for the format.
+    pub fn from_csv(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|err| {
+            ParseError::new(format!("Failed to read \"{}\": {err}", path.display()))
+        })?;
+
+        Self::from_csv_str(&content)
+    }
+
+    /// Parse samples from CSV data
+    ///
+    /// Only the first column is used. Values are either durations, such as "12.5ms",
+    /// or plain numbers in milliseconds. A header line, empty lines, and lines starting
+    /// with '#' are skipped.
+    pub fn from_csv_str(content: &str) -> Result<Self, ParseError> {
+        let mut samples = vec![];
+
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let value = line.split(',').next().unwrap().trim();
+            let sample = match value.parse::<f64>() {
+                Ok(millis) if millis.is_finite() && millis >= 0.0 => from_nanos_f64(millis * 1e6),
+                Ok(_) => {
+                    return Err(ParseError::new(format!(
+                        "Invalid latency \"{value}\" on line {}",
+                        idx + 1
+                    )));
+                }
+                Err(_) => match value.parse::<Duration>() {
+                    Ok(duration) => duration,
+                    // The first line may be a header
+                    Err(_) if samples.is_empty() && idx == 0 => continue,
+                    Err(err) => {
+                        return Err(ParseError::new(format!("{err} on line {}", idx + 1)));
+                    }
+                },
+            };
+
+            samples.push(sample);
+        }
+
+        if samples.is_empty() {
+            return Err(ParseError::new("No latency samples found"));
+        }
+
+        Ok(Self { samples })
+    }
+
+    pub fn get_samples(&self) -> &[Duration] {
+        &self.samples
+    }
+}
+
+impl LatencyModel for EmpiricalLatency {
+    fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        self.samples[rng.random_range(0..self.samples.len())]
+    }
+
+    fn mean(&self) -> Duration {
+        let total: u128 = self.samples.iter().map(|s| s.as_nanos() as u128).sum();
+        Duration::from_nanos((total / self.samples.len() as u128) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn sample_millis(model: &dyn LatencyModel) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(5);
+        (0..10_000)
+            .map(|_| model.sample(&mut rng).as_millis_f64())
+            .collect()
+    }
+
+    fn mean(samples: &[f64]) -> f64 {
+        samples.iter().sum::<f64>() / samples.len() as f64
+    }
+
+    fn median(mut samples: Vec<f64>) -> f64 {
+        samples.sort_by(f64::total_cmp);
+        samples[samples.len() / 2]
+    }
+
+    #[test]
+    fn distributions() {
+        let samples = sample_millis(&UniformLatency {
+            min: Duration::from_millis(10),
+            max: Duration::from_millis(20),
+        });
+        assert!(samples.iter().all(|s| (10.0..=20.0).contains(s)));
+        assert!((mean(&samples) - 15.0).abs() < 0.2);
+
+        let samples = sample_millis(&NormalLatency {
+            mean: Duration::from_millis(50),
+            std_dev: Duration::from_millis(5),
+        });
+        assert!((mean(&samples) - 50.0).abs() < 0.2);
+
+        let samples = sample_millis(&LogNormalLatency {
+            median: Duration::from_millis(30),
+            sigma: 0.5,
+        });
+        assert!((median(samples.clone()) - 30.0).abs() < 1.0);
+        assert!(mean(&samples) > 30.0);
+
+        let samples = sample_millis(&ParetoLatency {
+            min: Duration::from_millis(10),
+            shape: 3.0,
+        });
+        assert!(samples.iter().all(|s| *s >= 10.0));
+        // The mean is min * shape / (shape - 1)
+        assert!((mean(&samples) - 15.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn empirical_from_csv() {
+        let csv = "rtt,host\n10,a\n# comment\n\n12.5ms,b\n1s,c\n";
+        let model = EmpiricalLatency::from_csv_str(csv).unwrap();
+
+        assert_eq!(
+            model.get_samples(),
+            &[
+                Duration::from_millis(10),
+                Duration::from_micros(12_500),
+                Duration::from_seconds(1)
+            ]
+        );
+
+        let samples = sample_millis(&model);
+        assert!(samples.iter().all(|s| [10.0, 12.5, 1000.0].contains(s)));
+
+        assert!(EmpiricalLatency::from_csv_str("rtt\n").is_err());
+        assert!(EmpiricalLatency::from_csv_str("10\nfoo\n").is_err());
+        assert!(EmpiricalLatency::from_csv_str("-3\n").is_err());
+    }
+}
@@ -1,29 +1,71 @@
 use super::node::Node;
 
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering as AtomicOrdering};
 
+use crate::network::fault::LossState;
+use crate::network::latency::{ConstantLatency, LatencyModel};
 use crate::network::node::{DummyNodeData, NodeData};
-use crate::network::{DummyNetworkMessage, Latency, NetworkMessage, Object, ObjectId};
-use crate::time::Duration;
+use crate::network::routing::{topology_changed, Route};
+use crate::network::{
+    get_size_delay, Bandwidth, DummyNetworkMessage, FaultModel, Latency, LinkId, NetworkMessage,
+    NodeId, Object,
+};
+use crate::random::chance;
+use crate::time::{Duration, Time};
 
 /// Each link consists of two messages queues, one for each direction
+///
+/// Messages are first serialized onto the link, one after another, according to
+/// the bandwidth of their direction (if any). Then they take a latency, drawn from the
+/// link's [`LatencyModel`], to reach the other end.
+///
+/// A [`FaultModel`] can make the link lose, duplicate, or reorder messages.
+/// The link can also fail entirely for a while (see [`Link::set_down`]).
 pub struct Link<Message: NetworkMessage, Data: NodeData> {
-    identifier: ObjectId,
+    identifier: LinkId,
 
     queue1: Rc<LinkQueue<Message, Data>>,
     queue2: Rc<LinkQueue<Message, Data>>,
 
     callback: Box<dyn LinkCallback<Message, Data>>,
+    fault_model: RefCell<FaultModel>,
+    latency_model: RefCell<Box<dyn LatencyModel>>,
+    preserve_order: Cell<bool>,
+
+    /// Set while the link is down
+    down: Cell<Option<InFlight>>,
+    /// Incremented whenever the link goes down and drops messages in flight
+    drop_generation: Cell<u64>,
+    /// Deliveries and sends waiting for the link to come back up
+    held: RefCell<Vec<Box<dyn FnOnce()>>>,
 
     active_queues: AtomicU32,
 }
 
+/// What happens to messages while a link is down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InFlight {
+    /// Messages in flight and messages sent while the link is down are lost
+    Drop,
+    /// Messages are held back and delivered (or sent) once the link is up again
+    Hold,
+    /// Messages already in flight arrive as usual, but messages sent while the link is down are lost
+    Deliver,
+}
+
 pub trait LinkCallback<Message: NetworkMessage, Data: NodeData> {
-    fn message_sent(&self, _source: &ObjectId, _destination: &ObjectId, _message: &Message) {}
+    fn message_sent(&self, _source: &NodeId, _destination: &NodeId, _message: &Message) {}
     fn link_became_active(&self, _link: &Link<Message, Data>) {}
     fn link_became_inactive(&self, _link: &Link<Message, Data>) {}
+
+    /// A message was lost on the link, at the time it would have arrived
+    fn message_dropped(&self, _source: &NodeId, _destination: &NodeId, _message: &Message) {}
+
+    fn link_went_down(&self, _link: &Link<Message, Data>) {}
+    fn link_came_up(&self, _link: &Link<Message, Data>) {}
 }
 
 #[derive(Default)]
@@ -38,27 +80,156 @@ impl<Message: NetworkMessage, Data: NodeData> Link<Message, Data> {
         latency: Latency,
         callback: Box<dyn LinkCallback<Message, Data>>,
     ) -> Rc<Self> {
-        let queue1 = Rc::new(LinkQueue::new(latency, node1.clone(), node2.clone()));
+        let queue1 = Rc::new(LinkQueue::new(node1.clone(), node2.clone()));
 
-        let queue2 = Rc::new(LinkQueue::new(latency, node2, node1));
+        let queue2 = Rc::new(LinkQueue::new(node2, node1));
 
         let active_queues = AtomicU32::new(0);
 
         let obj = Rc::new(Self {
-            identifier: ObjectId::random(),
+            identifier: LinkId::allocate(),
             queue1,
             queue2,
             active_queues,
             callback,
+            fault_model: Default::default(),
+            latency_model: RefCell::new(Box::new(ConstantLatency(latency))),
+            preserve_order: Cell::new(false),
+            down: Cell::new(None),
+            drop_generation: Cell::new(0),
+            held: Default::default(),
         });
 
         let (node1, node2) = obj.get_nodes();
         node1.add_link(node2.get_identifier(), obj.clone());
         node2.add_link(node1.get_identifier(), obj.clone());
+        topology_changed();
 
         obj
     }
 
+    /// Limit the bandwidth for messages sent by the node with identifier `source`
+    ///
+    /// Pass None to remove the limit. This only affects messages sent afterwards.
+    /// Panics if the bandwidth is zero.
+    pub fn set_bandwidth(&self, source: &NodeId, bandwidth: Option<Bandwidth>) {
+        assert!(
+            bandwidth.is_none_or(|bandwidth| bandwidth.into_bits_per_second() > 0),
+            "Link bandwidth must not be zero"
+        );
+        self.get_queue(source).bandwidth.set(bandwidth);
+    }
+
+    /// The bandwidth for messages sent by the node with identifier `source`
+    pub fn get_bandwidth(&self, source: &NodeId) -> Option<Bandwidth> {
+        self.get_queue(source).bandwidth.get()
+    }
+
+    /// Make the link inject faults into messages sent afterwards
+    ///
+    /// The model applies to both directions, but each direction keeps its own loss state.
+    pub fn set_fault_model(&self, fault_model: FaultModel) {
+        fault_model.validate();
+        *self.fault_model.borrow_mut() = fault_model;
+    }
+
+    pub fn get_fault_model(&self) -> FaultModel {
+        self.fault_model.borrow().clone()
+    }
+
+    /// Draw the latency of messages sent afterwards from the given model
+    ///
+    /// The model applies to both directions.
+    pub fn set_latency_model(&self, latency_model: impl LatencyModel + 'static) {
+        *self.latency_model.borrow_mut() = Box::new(latency_model);
+        topology_changed();
+    }
+
+    /// The average latency of the link, which routing uses to find the fastest paths
+    pub fn get_mean_latency(&self) -> Duration {
+        self.latency_model.borrow().mean()
+    }
+
+    /// Keep messages in the order they were sent, even if their latencies vary
+    ///
+    /// A message then never arrives before a message sent earlier in the same direction,
+    /// as with TCP. Reordering faults still apply. This is disabled by default.
+    pub fn set_preserve_order(&self, preserve_order: bool) {
+        self.preserve_order.set(preserve_order);
+    }
+
+    fn get_queue(&self, source: &NodeId) -> &Rc<LinkQueue<Message, Data>> {
+        if self.queue1.get_source().get_identifier() == *source {
+            &self.queue1
+        } else if self.queue2.get_source().get_identifier() == *source {
+            &self.queue2
+        } else {
+            panic!("Node {source} is not connected to this link");
+        }
+    }
+
+    /// Make the link fail until [`Link::set_up`] is called
+    ///
+    /// `in_flight` decides what happens to messages in transit and messages sent
+    /// while the link is down. Calling this on a link that is down only changes `in_flight`.
+    pub fn set_down(&self, in_flight: InFlight) {
+        let was_up = self.down.replace(Some(in_flight)).is_none();
+
+        if in_flight == InFlight::Drop {
+            self.drop_generation.set(self.drop_generation.get() + 1);
+
+            // Messages held back so far are lost as well
+            let held = std::mem::take(&mut *self.held.borrow_mut());
+            for func in held {
+                func();
+            }
+        }
+
+        if was_up {
+            log::trace!("Link {} went down", self.identifier);
+            topology_changed();
+            self.callback.link_went_down(self);
+
+            let (node1, node2) = self.get_nodes();
+            node1
+                .get_callback()
+                .link_went_down(node1, node2.get_identifier());
+            node2
+                .get_callback()
+                .link_went_down(node2, node1.get_identifier());
+        }
+    }
+
+    /// Repair a link that is down
+    ///
+    /// Messages that were held back are delivered (or sent) in the order they were held.
+    pub fn set_up(&self) {
+        if self.down.take().is_none() {
+            return;
+        }
+
+        log::trace!("Link {} came up", self.identifier);
+        topology_changed();
+        self.callback.link_came_up(self);
+
+        let (node1, node2) = self.get_nodes();
+        node1
+            .get_callback()
+            .link_came_up(node1, node2.get_identifier());
+        node2
+            .get_callback()
+            .link_came_up(node2, node1.get_identifier());
+
+        let held = std::mem::take(&mut *self.held.borrow_mut());
+        for func in held {
+            func();
+        }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.down.get().is_none()
+    }
+
     /// Does the link currently have any messages in transit?
     pub fn is_active(&self) -> bool {
         self.active_queues.load(AtomicOrdering::Relaxed) > 0
@@ -78,13 +249,37 @@ impl<Message: NetworkMessage, Data: NodeData> Link<Message, Data> {
         }
     }
 
-    pub fn send(self_ptr: &Rc<Self>, source: ObjectId, message: Message) {
-        if self_ptr.queue1.get_source().get_identifier() == source {
-            LinkQueue::send(self_ptr.queue1.clone(), self_ptr.clone(), message);
-        } else if self_ptr.queue2.get_source().get_identifier() == source {
-            LinkQueue::send(self_ptr.queue2.clone(), self_ptr.clone(), message);
-        } else {
-            panic!("Invalid state");
+    pub fn send(self_ptr: &Rc<Self>, source: NodeId, message: Message) {
+        let route = Route {
+            origin: source,
+            destination: self_ptr
+                .get_queue(&source)
+                .get_destination()
+                .get_identifier(),
+        };
+        Self::send_routed(self_ptr, source, route, message);
+    }
+
+    /// Send a message that is (possibly) only one hop on its route
+    pub(super) fn send_routed(self_ptr: &Rc<Self>, source: NodeId, route: Route, message: Message) {
+        let queue = self_ptr.get_queue(&source).clone();
+
+        match self_ptr.down.get() {
+            None => {
+                LinkQueue::send(queue, self_ptr.clone(), route, message, false);
+            }
+            Some(InFlight::Hold) => {
+                let link = Rc::downgrade(self_ptr);
+                self_ptr.held.borrow_mut().push(Box::new(move || {
+                    if let Some(link) = link.upgrade() {
+                        Link::send_routed(&link, source, route, message);
+                    }
+                }));
+            }
+            Some(InFlight::Drop | InFlight::Deliver) => {
+                // The message is still sent, but gets lost on the way
+                LinkQueue::send(queue, self_ptr.clone(), route, message, true);
+            }
         }
     }
 
@@ -101,13 +296,21 @@ impl<Message: NetworkMessage, Data: NodeData> Link<Message, Data> {
 }
 
 impl<Message: NetworkMessage, Data: NodeData> Object for Link<Message, Data> {
-    fn get_identifier(&self) -> ObjectId {
+    type Identifier = LinkId;
+
+    fn get_identifier(&self) -> LinkId {
         self.identifier
     }
 }
 
 struct LinkQueue<Message: NetworkMessage, Data: NodeData> {
-    latency: Duration,
+    bandwidth: Cell<Option<Bandwidth>>,
+
+    /// When the last queued message will be fully serialized onto the link
+    busy_until: Cell<Time>,
+    /// When the last message sent will arrive (excluding reordering faults)
+    last_arrival: Cell<Time>,
+    loss_state: LossState,
 
     source: Rc<Node<Message, Data>>,
     dest: Rc<Node<Message, Data>>,
@@ -117,16 +320,15 @@ struct LinkQueue<Message: NetworkMessage, Data: NodeData> {
 }
 
 impl<Message: NetworkMessage, Data: NodeData> LinkQueue<Message, Data> {
-    fn new(
-        latency: Latency,
-        source: Rc<Node<Message, Data>>,
-        dest: Rc<Node<Message, Data>>,
-    ) -> Self {
+    fn new(source: Rc<Node<Message, Data>>, dest: Rc<Node<Message, Data>>) -> Self {
         let current_message_count = AtomicU32::new(0);
         let total_message_count = AtomicU64::new(0);
 
         Self {
-            latency,
+            bandwidth: Cell::new(None),
+            busy_until: Cell::new(Time::from_nanos(0)),
+            last_arrival: Cell::new(Time::from_nanos(0)),
+            loss_state: Default::default(),
             total_message_count,
             source,
             dest,
@@ -134,70 +336,152 @@ impl<Message: NetworkMessage, Data: NodeData> LinkQueue<Message, Data> {
         }
     }
 
+    /// Serialize a message onto the link and put it in transit
+    ///
+    /// If `lost` is set, the message is dropped (instead of delivered) when it arrives.
     fn send(
         self_ptr: Rc<LinkQueue<Message, Data>>,
         link: Rc<Link<Message, Data>>,
+        route: Route,
         message: Message,
+        lost: bool,
     ) -> (bool, Duration) {
-        let latency = self_ptr.latency;
-        //let size_delay = Self::get_size_delay(message.get_size(), self_ptr.bandwidth);
+        // Wait for all earlier messages to be serialized, then serialize this one
+        let now = crate::time::now();
+        let start = self_ptr.busy_until.get().max(now);
+        let done = match self_ptr.bandwidth.get() {
+            Some(bandwidth) => start + get_size_delay(message.get_size(), bandwidth),
+            None => start,
+        };
+        self_ptr.busy_until.set(done);
 
-        let was_empty = {
-            self_ptr
-                .total_message_count
-                .fetch_add(1, AtomicOrdering::Relaxed);
-            let prev = self_ptr
-                .current_message_count
-                .fetch_add(1, AtomicOrdering::Relaxed);
-            prev == 0
+        let latency = {
+            let latency_model = link.latency_model.borrow();
+            crate::random::with_rng(|rng| latency_model.sample(rng))
         };
 
+        let mut arrival = done + latency;
+        if link.preserve_order.get() {
+            arrival = arrival.max(self_ptr.last_arrival.get());
+        }
+        self_ptr.last_arrival.set(arrival);
+
+        let mut delay = arrival - now;
+
+        let (lost, duplicated) = {
+            let fault_model = link.fault_model.borrow();
+            let lost = lost || self_ptr.loss_state.is_lost(&fault_model.loss);
+            let duplicated = !lost && chance(fault_model.duplication);
+
+            if chance(fault_model.reordering) {
+                delay += fault_model.reordering_delay;
+            }
+
+            (lost, duplicated)
+        };
+
+        self_ptr
+            .total_message_count
+            .fetch_add(1, AtomicOrdering::Relaxed);
+
+        let mut was_empty = false;
+        if duplicated {
+            was_empty = Self::transmit(&self_ptr, &link, route, message.clone(), delay, lost);
+        }
+        was_empty |= Self::transmit(&self_ptr, &link, route, message, delay, lost);
+
+        (was_empty, delay)
+    }
+
+    /// Put a copy of a message in transit; it arrives (or is dropped) after `delay`
+    ///
+    /// Returns true if the queue was empty before
+    fn transmit(
+        self_ptr: &Rc<Self>,
+        link: &Rc<Link<Message, Data>>,
+        route: Route,
+        message: Message,
+        delay: Duration,
+        lost: bool,
+    ) -> bool {
+        let prev = self_ptr
+            .current_message_count
+            .fetch_add(1, AtomicOrdering::Relaxed);
+        let was_empty = prev == 0;
+
         if was_empty {
             let prev = link.active_queues.fetch_add(1, AtomicOrdering::SeqCst);
 
             if prev == 0 {
-                link.callback.link_became_active(&link);
+                link.callback.link_became_active(link);
             }
         }
 
+        let self_ptr = self_ptr.clone();
+        let link = link.clone();
+        let drop_generation = link.drop_generation.get();
+
         crate::spawn(async move {
-            // Sleep for how long the latency delays a message
-            if !latency.is_zero() {
-                crate::time::sleep(latency).await;
+            // Sleep for how long queueing, serialization, and latency delay the message
+            if !delay.is_zero() {
+                crate::time::sleep(delay).await;
             }
 
-            //TODO re-add link bandwidth
+            Self::arrive(self_ptr, link, route, message, lost, drop_generation);
+        });
 
-            let notify_delivery_fn = {
-                let self_ptr = self_ptr.clone();
+        was_empty
+    }
 
-                Box::new(move || {
-                    let prev = self_ptr
-                        .current_message_count
-                        .fetch_sub(1, AtomicOrdering::SeqCst);
-                    assert!(prev > 0);
+    /// Hand a message that reached the end of the link to its destination
+    ///
+    /// The message is dropped instead if it was lost, or if the link dropped
+    /// everything in flight since the message was sent.
+    fn arrive(
+        self_ptr: Rc<Self>,
+        link: Rc<Link<Message, Data>>,
+        route: Route,
+        message: Message,
+        lost: bool,
+        drop_generation: u64,
+    ) {
+        let source = self_ptr.source.get_identifier();
+        let dst = self_ptr.get_destination().clone();
+
+        if lost || link.drop_generation.get() != drop_generation {
+            link.callback
+                .message_dropped(&source, &dst.get_identifier(), &message);
+            self_ptr.message_done(&link);
+        } else if link.down.get() == Some(InFlight::Hold) {
+            // Held messages must not keep the link alive
+            let weak_link = Rc::downgrade(&link);
+            link.held.borrow_mut().push(Box::new(move || {
+                if let Some(link) = weak_link.upgrade() {
+                    Self::arrive(self_ptr, link, route, message, false, drop_generation);
+                }
+            }));
+        } else {
+            let notify_delivery_fn = Box::new(move || self_ptr.message_done(&link));
+            dst.deliver_message(route, message, notify_delivery_fn);
+        }
+    }
 
-                    // This was the message in this queue, so mark it as inactive
-                    if prev == 1 {
-                        let prev = link.active_queues.fetch_sub(1, AtomicOrdering::SeqCst);
+    /// A message in transit was delivered or dropped
+    fn message_done(&self, link: &Link<Message, Data>) {
+        let prev = self
+            .current_message_count
+            .fetch_sub(1, AtomicOrdering::SeqCst);
+        assert!(prev > 0);
 
-                        // No queues are active anymore, so mark the link as inactive
-                        if prev == 1 {
-                            link.callback.link_became_inactive(&link);
-                        }
-                    }
-                })
-            };
-
-            let dst = self_ptr.get_destination();
-            dst.deliver_message(
-                self_ptr.source.get_identifier(),
-                message,
-                notify_delivery_fn,
-            );
-        });
+        // This was the message in this queue, so mark it as inactive
+        if prev == 1 {
+            let prev = link.active_queues.fetch_sub(1, AtomicOrdering::SeqCst);
 
-        (was_empty, latency)
+            // No queues are active anymore, so mark the link as inactive
+            if prev == 1 {
+                link.callback.link_became_inactive(link);
+            }
+        }
     }
 
     fn get_source(&self) -> &Rc<Node<Message, Data>> {
@@ -211,13 +495,320 @@ impl<Message: NetworkMessage, Data: NodeData> LinkQueue<Message, Data> {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::{Cell, RefCell};
     use std::rc::Rc;
 
-    use crate::network::node::{DummyNodeCallback, DummyNodeData, Node};
-    use crate::network::{Bandwidth, DummyNetworkMessage, Object};
+    use crate::network::node::{DummyNodeCallback, DummyNodeData, Node, NodeCallback};
+    use crate::network::{
+        Bandwidth, DummyNetworkMessage, FaultModel, LossModel, NetworkMessage, NodeId, Object,
+        UniformLatency,
+    };
     use crate::time::Duration;
 
-    use super::{DummyLinkCallback, Link};
+    use super::{DummyLinkCallback, InFlight, Link, LinkCallback};
+
+    /// A numbered message of one megabyte
+    #[derive(Clone)]
+    struct LargeMessage(u32);
+
+    impl NetworkMessage for LargeMessage {
+        fn get_size(&self) -> u64 {
+            1024 * 1024
+        }
+    }
+
+    type Arrivals = Rc<RefCell<Vec<(u32, u64)>>>;
+
+    /// Records which messages arrive when
+    struct ArrivalCallback {
+        arrivals: Arrivals,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl NodeCallback<LargeMessage, DummyNodeData> for ArrivalCallback {
+        async fn handle_message(
+            &self,
+            _node: &Rc<Node<LargeMessage, DummyNodeData>>,
+            _source: NodeId,
+            message: LargeMessage,
+        ) {
+            self.arrivals
+                .borrow_mut()
+                .push((message.0, crate::time::now().to_millis()));
+        }
+    }
+
+    /// Counts lost messages and records when the link goes down or up
+    #[derive(Default)]
+    struct DropCallback {
+        dropped: Rc<Cell<u32>>,
+        is_up: Rc<RefCell<Vec<bool>>>,
+    }
+
+    impl LinkCallback<LargeMessage, DummyNodeData> for DropCallback {
+        fn message_dropped(&self, _source: &NodeId, _dest: &NodeId, _message: &LargeMessage) {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+
+        fn link_went_down(&self, _link: &Link<LargeMessage, DummyNodeData>) {
+            self.is_up.borrow_mut().push(false);
+        }
+
+        fn link_came_up(&self, _link: &Link<LargeMessage, DummyNodeData>) {
+            self.is_up.borrow_mut().push(true);
+        }
+    }
+
+    /// Connects two nodes (with fast NICs) and returns what the second one receives
+    #[allow(clippy::type_complexity)]
+    fn create_pair(
+        latency: Duration,
+        callback: DropCallback,
+    ) -> (
+        Rc<Node<LargeMessage, DummyNodeData>>,
+        Rc<Node<LargeMessage, DummyNodeData>>,
+        Rc<Link<LargeMessage, DummyNodeData>>,
+        Arrivals,
+        Arrivals,
+    ) {
+        let nic = Bandwidth::from_gigabits_per_second(1024);
+        let (arrivals1, arrivals2) = (Arrivals::default(), Arrivals::default());
+
+        let node1 = Node::new(
+            nic,
+            DummyNodeData::default(),
+            Box::new(ArrivalCallback {
+                arrivals: arrivals1.clone(),
+            }),
+        );
+        let node2 = Node::new(
+            nic,
+            DummyNodeData::default(),
+            Box::new(ArrivalCallback {
+                arrivals: arrivals2.clone(),
+            }),
+        );
+
+        let link = Node::connect(node1.clone(), node2.clone(), latency, Box::new(callback));
+        (node1, node2, link, arrivals1, arrivals2)
+    }
+
+    #[test]
+    fn bandwidth() {
+        crate::Runtime::new().block_on(async {
+            // Fast NICs behind a slow link
+            let (node1, node2, link, arrivals1, arrivals2) =
+                create_pair(Duration::from_millis(10), DropCallback::default());
+
+            // Only limit one direction
+            link.set_bandwidth(
+                &node1.get_identifier(),
+                Some(Bandwidth::from_megabytes_per_second(1)),
+            );
+            assert_eq!(link.get_bandwidth(&node2.get_identifier()), None);
+
+            node1.send_to(&node2.get_identifier(), LargeMessage(0));
+            node1.send_to(&node2.get_identifier(), LargeMessage(1));
+            node2.send_to(&node1.get_identifier(), LargeMessage(2));
+
+            crate::time::sleep(Duration::from_seconds(3)).await;
+
+            // The second message waits for the first one to be serialized
+            assert_eq!(*arrivals2.borrow(), vec![(0, 1010), (1, 2010)]);
+            assert_eq!(*arrivals1.borrow(), vec![(2, 10)]);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Link bandwidth must not be zero")]
+    fn zero_bandwidth() {
+        crate::Runtime::new().block_on(async {
+            let (node1, _, link, _, _) =
+                create_pair(Duration::from_millis(10), DropCallback::default());
+            link.set_bandwidth(
+                &node1.get_identifier(),
+                Some(Bandwidth::from_bits_per_second(0)),
+            );
+        });
+    }
+
+    #[test]
+    fn loss() {
+        let runtime = crate::Runtime::new();
+        runtime.set_seed(1);
+
+        runtime.block_on(async {
+            let callback = DropCallback::default();
+            let dropped = callback.dropped.clone();
+            let (node1, node2, link, _, arrivals) =
+                create_pair(Duration::from_millis(10), callback);
+
+            link.set_fault_model(FaultModel {
+                loss: LossModel::Bernoulli(0.3),
+                ..Default::default()
+            });
+
+            for idx in 0..1000 {
+                node1.send_to(&node2.get_identifier(), LargeMessage(idx));
+            }
+            crate::time::sleep(Duration::from_seconds(1)).await;
+
+            assert!((250..350).contains(&dropped.get()));
+            assert_eq!(arrivals.borrow().len() as u32 + dropped.get(), 1000);
+            assert!(!link.is_active());
+
+            // Alternate between the good and the bad state
+            link.set_fault_model(FaultModel {
+                loss: LossModel::GilbertElliott {
+                    good_to_bad: 1.0,
+                    bad_to_good: 1.0,
+                    loss_good: 0.0,
+                    loss_bad: 1.0,
+                },
+                ..Default::default()
+            });
+
+            arrivals.borrow_mut().clear();
+            for idx in 0..6 {
+                node1.send_to(&node2.get_identifier(), LargeMessage(idx));
+            }
+            crate::time::sleep(Duration::from_seconds(1)).await;
+
+            let received: Vec<_> = arrivals.borrow().iter().map(|(idx, _)| *idx).collect();
+            assert_eq!(received, vec![1, 3, 5]);
+        });
+    }
+
+    #[test]
+    fn duplication_and_reordering() {
+        crate::Runtime::new().block_on(async {
+            let (node1, node2, link, _, arrivals) =
+                create_pair(Duration::from_millis(10), DropCallback::default());
+
+            link.set_fault_model(FaultModel {
+                duplication: 1.0,
+                reordering: 1.0,
+                reordering_delay: Duration::from_millis(5),
+                ..Default::default()
+            });
+            node1.send_to(&node2.get_identifier(), LargeMessage(0));
+
+            link.set_fault_model(FaultModel::default());
+            node1.send_to(&node2.get_identifier(), LargeMessage(1));
+
+            crate::time::sleep(Duration::from_seconds(1)).await;
+
+            assert_eq!(*arrivals.borrow(), vec![(1, 10), (0, 15), (0, 15)]);
+            assert!(!link.is_active());
+        });
+    }
+
+    #[test]
+    fn jitter() {
+        let runtime = crate::Runtime::new();
+        runtime.set_seed(3);
+
+        runtime.block_on(async {
+            let (node1, node2, link, _, arrivals) =
+                create_pair(Duration::ZERO, DropCallback::default());
+            link.set_latency_model(UniformLatency {
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(100),
+            });
+
+            let mut received_in_order = vec![];
+
+            for preserve_order in [false, true] {
+                link.set_preserve_order(preserve_order);
+                arrivals.borrow_mut().clear();
+
+                for idx in 0..20 {
+                    node1.send_to(&node2.get_identifier(), LargeMessage(idx));
+                    crate::time::sleep(Duration::from_millis(1)).await;
+                }
+                crate::time::sleep(Duration::from_seconds(1)).await;
+
+                let arrivals = arrivals.borrow();
+                assert_eq!(arrivals.len(), 20);
+                assert!(arrivals.iter().all(|(idx, time)| *time >= 10 + *idx as u64));
+                received_in_order.push(arrivals.is_sorted());
+            }
+
+            assert_eq!(received_in_order, vec![false, true]);
+        });
+    }
+
+    #[test]
+    fn down_and_up() {
+        for (in_flight, expected, num_dropped) in [
+            (InFlight::Drop, vec![], 2),
+            (InFlight::Hold, vec![(0, 20), (1, 30)], 0),
+            (InFlight::Deliver, vec![(0, 10)], 1),
+        ] {
+            crate::Runtime::new().block_on(async move {
+                let callback = DropCallback::default();
+                let (dropped, is_up) = (callback.dropped.clone(), callback.is_up.clone());
+                let (node1, node2, link, _, arrivals) =
+                    create_pair(Duration::from_millis(10), callback);
+
+                node1.send_to(&node2.get_identifier(), LargeMessage(0));
+                crate::time::sleep(Duration::from_millis(5)).await;
+
+                link.set_down(in_flight);
+                assert!(!link.is_up());
+                node1.send_to(&node2.get_identifier(), LargeMessage(1));
+
+                // Messages are only dropped once they would have arrived
+                crate::time::sleep(Duration::from_millis(1)).await;
+                assert_eq!(dropped.get(), 0, "{in_flight:?}");
+
+                crate::time::sleep(Duration::from_millis(14)).await;
+                link.set_up();
+                crate::time::sleep(Duration::from_millis(100)).await;
+
+                assert_eq!(*arrivals.borrow(), expected, "{in_flight:?}");
+                assert_eq!(dropped.get(), num_dropped, "{in_flight:?}");
+                assert_eq!(*is_up.borrow(), vec![false, true]);
+                assert_eq!(link.num_total_messages(), 2);
+                assert!(!link.is_active());
+            });
+        }
+    }
+
+    #[test]
+    fn hold_then_drop() {
+        crate::Runtime::new().block_on(async {
+            let callback = DropCallback::default();
+            let dropped = callback.dropped.clone();
+            let (node1, node2, link, _, arrivals) =
+                create_pair(Duration::from_millis(10), callback);
+
+            // One message arrives while the link is down, the other one is sent then
+            node1.send_to(&node2.get_identifier(), LargeMessage(0));
+            crate::time::sleep(Duration::from_millis(5)).await;
+            link.set_down(InFlight::Hold);
+            node1.send_to(&node2.get_identifier(), LargeMessage(1));
+            crate::time::sleep(Duration::from_millis(15)).await;
+
+            link.set_down(InFlight::Drop);
+            link.set_up();
+            crate::time::sleep(Duration::from_millis(100)).await;
+
+            assert!(arrivals.borrow().is_empty());
+            assert_eq!(dropped.get(), 2);
+            assert!(!link.is_active());
+
+            // Messages held by a link do not keep it alive
+            link.set_down(InFlight::Hold);
+            node1.send_to(&node2.get_identifier(), LargeMessage(2));
+            crate::time::sleep(Duration::from_millis(1)).await;
+
+            let weak_link = Rc::downgrade(&link);
+            drop(link);
+            node1.disconnect_all();
+            assert!(weak_link.upgrade().is_none());
+        });
+    }
 
     #[test]
     fn is_active() {
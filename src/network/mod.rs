@@ -3,24 +3,57 @@
 /// There are two important primitives in this module
 ///     * Nodes represent individual nodes in the network
 ///     * Links are connection between the nodes
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::parse::{parse_scaled, split_quantity, ParseError};
 use crate::time::Duration;
 
 mod node;
 pub use node::{DummyNodeCallback, DummyNodeData, Node, NodeCallback, NodeData};
 
 mod link;
-pub use link::{DummyLinkCallback, Link, LinkCallback};
+pub use link::{DummyLinkCallback, InFlight, Link, LinkCallback};
+
+mod registry;
+pub use registry::Network;
+
+mod partition;
+pub use partition::Partition;
+
+mod fault;
+pub use fault::{FaultModel, LossModel};
+
+mod latency;
+pub use latency::{
+    ConstantLatency, EmpiricalLatency, LatencyModel, LogNormalLatency, NormalLatency,
+    ParetoLatency, UniformLatency,
+};
+
+mod routing;
+
+pub mod topology;
 
 mod object;
-pub use object::{Object, ObjectId};
+pub(crate) use object::IdAllocator;
+pub use object::{LinkId, NodeId, Object};
 
 /// Network latency in milliseconds
 pub type Latency = Duration;
 
+/// Network bandwidth in bits per second
+///
+/// Note, prefixes are binary, i.e., a megabit is 1024*1024 bits
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Bandwidth(u64);
 
 impl Bandwidth {
+    pub fn from_bits_per_second(bps: u64) -> Self {
+        Self(bps)
+    }
+
     pub fn from_megabytes_per_second(mbps: u64) -> Self {
         Self::from_megabits_per_second(8 * mbps)
     }
@@ -29,11 +62,105 @@ impl Bandwidth {
         Self(mbps * 1024 * 1024)
     }
 
+    pub fn from_gigabits_per_second(gbps: u64) -> Self {
+        Self::from_megabits_per_second(1024 * gbps)
+    }
+
     pub fn into_bits_per_second(self) -> u64 {
         self.0
     }
 }
 
+/// Prefixes that can be used when writing bandwidths
+///
+/// Sorted from largest to smallest
+const BANDWIDTH_PREFIXES: [(&str, u64); 5] = [
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+    ("", 1),
+];
+
+/// Writes the bandwidth using the largest prefix that represents it exactly
+/// (e.g., "100Mbit/s")
+impl std::fmt::Display for Bandwidth {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let (prefix, scale) = if self.0 == 0 {
+            &("", 1)
+        } else {
+            BANDWIDTH_PREFIXES
+                .iter()
+                .find(|(_, scale)| self.0.is_multiple_of(*scale))
+                .expect("Every bandwidth is a multiple of one")
+        };
+
+        write!(fmt, "{}{prefix}bit/s", self.0 / scale)
+    }
+}
+
+/// Parses bandwidths such as "100Mbit/s", "1Gbps", or "2.5MB/s"
+///
+/// Units ending in "bit/s" or "bps" are in bits,
+/// units ending in "B/s" or "Bps" are in bytes.
+impl FromStr for Bandwidth {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, ParseError> {
+        let invalid_unit =
+            || ParseError::new(format!("Invalid or missing bandwidth unit in \"{input}\""));
+        let (number, unit) = split_quantity(input)?;
+
+        let (prefix, bits_per_unit) = if let Some(prefix) = unit
+            .strip_suffix("bit/s")
+            .or_else(|| unit.strip_suffix("bps"))
+        {
+            (prefix, 1)
+        } else if let Some(prefix) = unit
+            .strip_suffix("B/s")
+            .or_else(|| unit.strip_suffix("Bps"))
+        {
+            (prefix, 8)
+        } else {
+            return Err(invalid_unit());
+        };
+
+        let prefix = if prefix == "k" { "K" } else { prefix };
+        let Some((_, scale)) = BANDWIDTH_PREFIXES.iter().find(|(name, _)| *name == prefix) else {
+            return Err(invalid_unit());
+        };
+
+        parse_scaled(number, scale * bits_per_unit).map(Self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl crate::parse::Quantity for Bandwidth {
+    const EXPECTING: &'static str = "a bandwidth in bits per second or a string like \"100Mbit/s\"";
+
+    fn from_raw(bps: u64) -> Self {
+        Self(bps)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Bandwidth {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::parse::serialize_quantity(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Bandwidth {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::parse::deserialize_quantity(deserializer)
+    }
+}
+
 pub trait NetworkMessage: Clone + 'static {
     fn get_size(&self) -> u64;
 }
@@ -48,10 +175,11 @@ impl NetworkMessage for DummyNetworkMessage {
 }
 
 pub fn get_size_delay(size: u64, bandwidth: Bandwidth) -> Duration {
-    // Converts bandwidth bits / microsecond and size to bits
-    let micros = (size * 8 * 1000 * 1000) / bandwidth.into_bits_per_second();
+    // Converts size to bits and computes the delay in nanoseconds
+    // (using 128-bit integers to not overflow for large messages)
+    let nanos = (size as u128 * 8 * 1_000_000_000) / (bandwidth.into_bits_per_second() as u128);
 
-    Duration::from_micros(micros)
+    Duration::from_nanos(nanos.try_into().expect("Size delay is too long"))
 }
 
 #[cfg(test)]
@@ -72,4 +200,30 @@ mod tests {
         // 8*3 == 24
         assert_eq!(delay, Duration::from_seconds(1));
     }
+
+    #[test]
+    fn bandwidth_from_str() {
+        assert_eq!(
+            "100Mbit/s".parse(),
+            Ok(Bandwidth::from_megabits_per_second(100))
+        );
+        assert_eq!("1Gbps".parse(), Ok(Bandwidth::from_gigabits_per_second(1)));
+        assert_eq!("2MB/s".parse(), Ok(Bandwidth::from_megabytes_per_second(2)));
+        assert_eq!(
+            "1.5 kbps".parse(),
+            Ok(Bandwidth::from_bits_per_second(1536))
+        );
+        assert!("100Mb".parse::<Bandwidth>().is_err());
+        assert!("100Xbit/s".parse::<Bandwidth>().is_err());
+    }
+
+    #[test]
+    fn bandwidth_display() {
+        let bandwidth = Bandwidth::from_megabits_per_second(100);
+        assert_eq!(bandwidth.to_string(), "100Mbit/s");
+        assert_eq!(bandwidth.to_string().parse(), Ok(bandwidth));
+
+        let bandwidth = Bandwidth::from_bits_per_second(1000);
+        assert_eq!(bandwidth.to_string(), "1000bit/s");
+    }
 }
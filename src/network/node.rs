@@ -1,17 +1,21 @@
 use std::any::Any;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
 use crate::sync::mpsc;
+use crate::time::{Duration, LocalClock, SleepFut, Time};
 
 use crate::network::{get_size_delay, Bandwidth, DummyNetworkMessage, Latency, NetworkMessage};
 
 use crate::network::link::{Link, LinkCallback};
-use crate::network::{Object, ObjectId};
+use crate::network::routing::{topology_changed, Route, RoutingTable};
+use crate::network::{NodeId, Object};
 
 pub type NotifyDeliveryFn = Box<dyn FnOnce()>;
 
+type InboxEntry<Message> = (Route, Message, NotifyDeliveryFn);
+
 /// Implement this trait to add custom logic to a node
 #[ async_trait::async_trait(?Send) ]
 pub trait NodeCallback<Message: NetworkMessage, Data: NodeData>: Any {
@@ -21,11 +25,31 @@ pub trait NodeCallback<Message: NetworkMessage, Data: NodeData>: Any {
     async fn handle_message(
         &self,
         _node: &Rc<Node<Message, Data>>,
-        _source: ObjectId,
+        _source: NodeId,
         message: Message,
     );
 
-    fn peer_disconnected(&self, _node: &Node<Message, Data>, _peer: ObjectId) {}
+    fn peer_disconnected(&self, _node: &Node<Message, Data>, _peer: NodeId) {}
+
+    /// A message from `origin` to `destination` passes through this node
+    /// (see [`Node::send_to_any`])
+    ///
+    /// Return false to drop the message. This can also sleep to model processing delay.
+    async fn forward_message(
+        &self,
+        _node: &Rc<Node<Message, Data>>,
+        _origin: NodeId,
+        _destination: NodeId,
+        _message: &Message,
+    ) -> bool {
+        true
+    }
+
+    /// The link to `peer` failed (see [`Link::set_down`])
+    fn link_went_down(&self, _node: &Node<Message, Data>, _peer: NodeId) {}
+
+    /// The link to `peer` was repaired
+    fn link_came_up(&self, _node: &Node<Message, Data>, _peer: NodeId) {}
 }
 
 #[derive(Default)]
@@ -36,7 +60,7 @@ impl NodeCallback<DummyNetworkMessage, DummyNodeData> for DummyNodeCallback {
     async fn handle_message(
         &self,
         _node: &Rc<Node<DummyNetworkMessage, DummyNodeData>>,
-        _source: ObjectId,
+        _source: NodeId,
         _message: DummyNetworkMessage,
     ) {
     }
@@ -53,33 +77,47 @@ impl NodeData for DummyNodeData {}
 /// A Node represents a node in the network
 /// It can communicate with other nodes using a Link
 pub struct Node<Message: NetworkMessage, Data: NodeData> {
-    identifier: ObjectId,
-    inbox_sender: mpsc::Sender<(ObjectId, Message, NotifyDeliveryFn)>,
+    identifier: NodeId,
+    name: RefCell<Option<String>>,
+    inbox_sender: mpsc::UnboundedSender<InboxEntry<Message>>,
     bandwidth: Bandwidth,
+    uplink_bandwidth: Cell<Option<Bandwidth>>,
+    /// When the last outgoing message will be fully sent by the uplink
+    uplink_busy_until: Cell<Time>,
     data: Data,
     callback: Box<dyn NodeCallback<Message, Data>>,
-    network_links: RefCell<HashMap<ObjectId, Rc<Link<Message, Data>>>>,
+    /// Sorted by peer, so that broadcasts are sent in a deterministic order
+    network_links: RefCell<BTreeMap<NodeId, Rc<Link<Message, Data>>>>,
+    local_clock: RefCell<Option<LocalClock>>,
+    routing_table: RefCell<Option<RoutingTable>>,
 }
 
 impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
     /// Create a new node
     ///
-    /// * bandwidth: The network bandwidth of this node
+    /// * bandwidth: The network bandwidth of this node, i.e., of its NIC, for incoming messages
+    ///   (use [`Node::set_uplink_bandwidth`] to limit outgoing messages
+    ///   and [`Link::set_bandwidth`] to limit individual links)
     /// * logic: The custom logic for your simulation
     pub fn new(
         bandwidth: Bandwidth,
         data: Data,
         callback: Box<dyn NodeCallback<Message, Data>>,
     ) -> Rc<Self> {
-        let (inbox_sender, inbox_receiver) = mpsc::channel();
+        let (inbox_sender, inbox_receiver) = mpsc::unbounded_channel();
 
         let obj = Rc::new(Self {
-            identifier: ObjectId::random(),
+            identifier: NodeId::allocate(),
+            name: RefCell::new(None),
             bandwidth,
+            uplink_bandwidth: Cell::new(None),
+            uplink_busy_until: Cell::new(Time::from_nanos(0)),
             inbox_sender,
             callback,
             data,
-            network_links: RefCell::new(HashMap::default()),
+            network_links: RefCell::new(BTreeMap::default()),
+            local_clock: RefCell::new(None),
+            routing_table: RefCell::new(None),
         });
 
         obj.callback.node_started(&*obj);
@@ -104,8 +142,6 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
         let mut links = self.network_links.borrow_mut();
 
         for (peer_id, link) in links.iter() {
-            log::trace!("Disconnecting node {} and {}", self.identifier, peer_id);
-
             let (node1, node2) = link.get_nodes();
 
             let node = if node1.get_identifier() == *peer_id {
@@ -116,6 +152,8 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
                 panic!("Invalid state");
             };
 
+            log::trace!("Disconnecting node {self} and {node}");
+
             node.network_links
                 .borrow_mut()
                 .remove(&self.identifier)
@@ -125,10 +163,12 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
         }
 
         links.clear();
+        topology_changed();
     }
 
     /// Connect this node to another one
     ///
+    /// Every message takes `link_latency`; use [`Link::set_latency_model`] to vary it.
     /// Returns a reference to the newly created link
     pub fn connect(
         node1: Rc<Self>,
@@ -136,53 +176,61 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
         link_latency: Latency,
         callback: Box<dyn LinkCallback<Message, Data>>,
     ) -> Rc<Link<Message, Data>> {
-        log::trace!(
-            "Connecting node {} and {}",
-            node1.get_identifier(),
-            node2.get_identifier()
-        );
+        log::trace!("Connecting node {node1} and {node2}");
 
         Link::new(node1, node2, link_latency, callback)
     }
 
     pub(super) fn deliver_message(
         &self,
-        source: ObjectId,
+        route: Route,
         message: Message,
         notify_delivery_fn: NotifyDeliveryFn,
     ) {
-        self.inbox_sender
-            .send((source, message, notify_delivery_fn));
+        // The node holds its own inbox, so this cannot fail
+        let _ = self.inbox_sender.send((route, message, notify_delivery_fn));
     }
 
-    async fn inbox_loop(
-        self_ptr: Rc<Self>,
-        inbox_receiver: mpsc::Receiver<(ObjectId, Message, NotifyDeliveryFn)>,
-    ) {
-        loop {
-            for (source, message, notify_delivery_fn) in inbox_receiver.recv().await.drain(..) {
-                let size = message.get_size();
-                let size_delay = get_size_delay(size, self_ptr.bandwidth);
+    async fn inbox_loop(self_ptr: Rc<Self>, inbox_receiver: mpsc::Receiver<InboxEntry<Message>>) {
+        while let Some((route, message, notify_delivery_fn)) = inbox_receiver.recv().await {
+            let size = message.get_size();
+            let size_delay = get_size_delay(size, self_ptr.bandwidth);
 
-                if !size_delay.is_zero() {
-                    crate::time::sleep(size_delay).await;
-                }
+            if !size_delay.is_zero() {
+                crate::time::sleep(size_delay).await;
+            }
 
-                notify_delivery_fn();
+            notify_delivery_fn();
 
-                let self_ptr2 = self_ptr.clone();
+            let self_ptr2 = self_ptr.clone();
+
+            if route.destination == self_ptr.identifier {
                 crate::spawn(async move {
                     self_ptr2
                         .callback
-                        .handle_message(&self_ptr2, source, message)
+                        .handle_message(&self_ptr2, route.origin, message)
                         .await;
                 });
+            } else {
+                crate::spawn(async move {
+                    let forward = self_ptr2
+                        .callback
+                        .forward_message(&self_ptr2, route.origin, route.destination, &message)
+                        .await;
+
+                    if forward && !self_ptr2.send_routed(route, message) {
+                        log::warn!(
+                            "Node {self_ptr2} cannot forward message to {}: no route",
+                            route.destination
+                        );
+                    }
+                });
             }
         }
     }
 
     /// Returns the connection to another node with the specified identifier (if it exists)
-    pub fn get_link_to(&self, node_id: &ObjectId) -> Option<Rc<Link<Message, Data>>> {
+    pub fn get_link_to(&self, node_id: &NodeId) -> Option<Rc<Link<Message, Data>>> {
         if *node_id == self.identifier {
             panic!("There cannot be a link to the node itself");
         }
@@ -190,10 +238,7 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
         match self.network_links.borrow().get(node_id) {
             Some(link) => Some(link.clone()),
             None => {
-                log::warn!(
-                    "There exists no network link from node {} to {node_id}",
-                    self.identifier
-                );
+                log::warn!("There exists no network link from node {self} to {node_id}");
                 None
             }
         }
@@ -202,16 +247,20 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
     /// Send a message to the node with the specified identifier
     ///
     /// Returns false if no connection to the node existed
-    pub fn send_to<M: Into<Message>>(&self, node_id: &ObjectId, message: M) -> bool {
+    pub fn send_to<M: Into<Message>>(&self, node_id: &NodeId, message: M) -> bool {
         if let Some(link) = self.get_link_to(node_id) {
-            Link::send(&link, self.identifier, message.into());
+            let route = Route {
+                origin: self.identifier,
+                destination: *node_id,
+            };
+            self.send_on(link, route, message.into());
             true
         } else {
             false
         }
     }
 
-    pub fn broadcast(&self, message: Message, ignore: Option<ObjectId>) {
+    pub fn broadcast(&self, message: Message, ignore: Option<NodeId>) {
         let links = self.network_links.borrow();
 
         if links.is_empty() {
@@ -235,14 +284,113 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
                 }
             }
 
-            Link::send(link, self.get_identifier(), message.clone());
+            let route = Route {
+                origin: self.identifier,
+                destination: *id,
+            };
+            self.send_on(link.clone(), route, message.clone());
+        }
+    }
+
+    /// Send a message to any node that can be reached from this one, possibly over multiple hops
+    ///
+    /// Messages follow the path with the lowest average latency, paying latency and
+    /// bandwidth on every link. Nodes along the way can intercept them with
+    /// [`NodeCallback::forward_message`].
+    ///
+    /// Returns false if there is no path to the node
+    pub fn send_to_any<M: Into<Message>>(&self, node_id: &NodeId, message: M) -> bool {
+        if *node_id == self.identifier {
+            panic!("Cannot send a message to the node itself");
         }
+
+        let route = Route {
+            origin: self.identifier,
+            destination: *node_id,
+        };
+        self.send_routed(route, message.into())
+    }
+
+    /// The neighbor messages to the given node are forwarded to (if the node is reachable)
+    pub fn get_next_hop(&self, node_id: &NodeId) -> Option<NodeId> {
+        let mut routing_table = self.routing_table.borrow_mut();
+
+        if !routing_table
+            .as_ref()
+            .is_some_and(|table| table.is_current())
+        {
+            *routing_table = Some(RoutingTable::compute(self));
+        }
+
+        routing_table.as_ref().unwrap().next_hop(node_id)
+    }
+
+    /// Send a message towards its destination
+    ///
+    /// Returns false if there is no route
+    fn send_routed(&self, route: Route, message: Message) -> bool {
+        let Some(next_hop) = self.get_next_hop(&route.destination) else {
+            return false;
+        };
+
+        let link = self.network_links.borrow().get(&next_hop).cloned();
+        let link = link.expect("Routing table is out of date");
+
+        self.send_on(link, route, message);
+        true
+    }
+
+    /// Limit the bandwidth of this node for outgoing messages
+    ///
+    /// Outgoing messages are then sent one after another, before they enter their link.
+    /// Pass None to remove the limit (the default). Panics if the bandwidth is zero.
+    pub fn set_uplink_bandwidth(&self, bandwidth: Option<Bandwidth>) {
+        assert!(
+            bandwidth.is_none_or(|bandwidth| bandwidth.into_bits_per_second() > 0),
+            "Uplink bandwidth must not be zero"
+        );
+        self.uplink_bandwidth.set(bandwidth);
+    }
+
+    /// The bandwidth of this node for outgoing messages (if limited)
+    pub fn get_uplink_bandwidth(&self) -> Option<Bandwidth> {
+        self.uplink_bandwidth.get()
+    }
+
+    /// The bandwidth of this node for incoming messages
+    pub fn get_downlink_bandwidth(&self) -> Bandwidth {
+        self.bandwidth
+    }
+
+    /// Pass a message through the uplink and then onto the link
+    fn send_on(&self, link: Rc<Link<Message, Data>>, route: Route, message: Message) {
+        let Some(bandwidth) = self.uplink_bandwidth.get() else {
+            Link::send_routed(&link, self.identifier, route, message);
+            return;
+        };
+
+        // Wait for all earlier messages to be sent, then send this one
+        let now = crate::time::now();
+        let start = self.uplink_busy_until.get().max(now);
+        let done = start + get_size_delay(message.get_size(), bandwidth);
+        self.uplink_busy_until.set(done);
+
+        if done == now {
+            Link::send_routed(&link, self.identifier, route, message);
+            return;
+        }
+
+        let source = self.identifier;
+        crate::spawn(async move {
+            crate::time::sleep(done - now).await;
+            Link::send_routed(&link, source, route, message);
+        });
     }
 
     /// Let the node know a new network connection exists
     ///
     /// Should only be called by Link's constructor
-    pub(crate) fn add_link(&self, dest: ObjectId, link: Rc<Link<Message, Data>>) {
+    pub(crate) fn add_link(&self, dest: NodeId, link: Rc<Link<Message, Data>>) {
         let mut network_links = self.network_links.borrow_mut();
         let prev = network_links.insert(dest, link);
 
@@ -265,16 +413,59 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
             .expect("Incompatible callback type")
     }
 
+    /// Give this node a human-readable name, e.g., for logging
+    ///
+    /// Names do not have to be unique. They are shown when displaying the node.
+    pub fn set_name(&self, name: impl Into<String>) {
+        *self.name.borrow_mut() = Some(name.into());
+    }
+
+    pub fn get_name(&self) -> Option<String> {
+        self.name.borrow().clone()
+    }
+
     pub fn get_data(&self) -> &Data {
         &self.data
     }
 
-    /// Returns which nodes this node is connected to
-    pub fn get_peers(&self) -> Vec<ObjectId> {
+    /// Give this node its own (imperfect) clock
+    ///
+    /// Without a local clock, the node uses the global simulated time
+    pub fn set_local_clock(&self, clock: LocalClock) {
+        *self.local_clock.borrow_mut() = Some(clock);
+    }
+
+    /// Get the current time as seen by this node
+    pub fn local_now(&self) -> Time {
+        let now = crate::time::now();
+
+        match &*self.local_clock.borrow() {
+            Some(clock) => clock.local_time(now),
+            None => now,
+        }
+    }
+
+    /// Wait until the specified duration has passed on this node's clock
+    pub fn local_sleep(&self, duration: Duration) -> SleepFut {
+        let duration = match &*self.local_clock.borrow() {
+            Some(clock) => clock.to_global_duration(duration),
+            None => duration,
+        };
+
+        crate::time::sleep(duration)
+    }
+
+    /// Returns which nodes this node is connected to, sorted by identifier
+    pub fn get_peers(&self) -> Vec<NodeId> {
         let links = self.network_links.borrow();
         links.keys().copied().collect()
     }
 
+    /// Returns the links of this node
+    pub(super) fn get_links(&self) -> Vec<Rc<Link<Message, Data>>> {
+        self.network_links.borrow().values().cloned().collect()
+    }
+
     /// How many other nodes is this node connected to?
     pub fn num_peers(&self) -> usize {
         let links = self.network_links.borrow();
@@ -283,11 +474,23 @@ impl<Message: NetworkMessage, Data: NodeData> Node<Message, Data> {
 }
 
 impl<Message: NetworkMessage, Data: NodeData> Object for Node<Message, Data> {
-    fn get_identifier(&self) -> ObjectId {
+    type Identifier = NodeId;
+
+    fn get_identifier(&self) -> NodeId {
         self.identifier
     }
 }
 
+/// Shows the node's name, or its identifier if it has none
+impl<Message: NetworkMessage, Data: NodeData> std::fmt::Display for Node<Message, Data> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match &*self.name.borrow() {
+            Some(name) => write!(fmt, "{name}"),
+            None => write!(fmt, "{}", self.identifier),
+        }
+    }
+}
+
 impl<Message: NetworkMessage, Data: NodeData> std::ops::Deref for Node<Message, Data> {
     type Target = Data;
 
@@ -295,3 +498,163 @@ impl<Message: NetworkMessage, Data: NodeData> std::ops::Deref for Node<Message,
         self.get_data()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::{DummyNodeData, Node, NodeCallback};
+    use crate::network::{Bandwidth, InFlight, LinkCallback, NetworkMessage, NodeId, Object};
+    use crate::time::Duration;
+
+    /// A message of one megabyte
+    #[derive(Clone)]
+    struct LargeMessage;
+
+    impl NetworkMessage for LargeMessage {
+        fn get_size(&self) -> u64 {
+            1024 * 1024
+        }
+    }
+
+    /// Records where messages came from and when they arrived,
+    /// and counts the messages this node forwarded
+    #[derive(Clone, Default)]
+    struct ArrivalCallback {
+        arrivals: Rc<RefCell<Vec<(NodeId, u64)>>>,
+        forwarded: Rc<Cell<u32>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl NodeCallback<LargeMessage, DummyNodeData> for ArrivalCallback {
+        async fn handle_message(
+            &self,
+            _node: &Rc<Node<LargeMessage, DummyNodeData>>,
+            source: NodeId,
+            _message: LargeMessage,
+        ) {
+            self.arrivals
+                .borrow_mut()
+                .push((source, crate::time::now().to_millis()));
+        }
+
+        async fn forward_message(
+            &self,
+            _node: &Rc<Node<LargeMessage, DummyNodeData>>,
+            _origin: NodeId,
+            _destination: NodeId,
+            _message: &LargeMessage,
+        ) -> bool {
+            self.forwarded.set(self.forwarded.get() + 1);
+            true
+        }
+    }
+
+    fn create_node(callback: &ArrivalCallback) -> Rc<Node<LargeMessage, DummyNodeData>> {
+        // Fast enough to not matter
+        Node::new(
+            Bandwidth::from_gigabits_per_second(1024),
+            DummyNodeData::default(),
+            Box::new(callback.clone()),
+        )
+    }
+
+    struct NoopLinkCallback;
+
+    impl LinkCallback<LargeMessage, DummyNodeData> for NoopLinkCallback {}
+
+    #[test]
+    fn uplink_bandwidth() {
+        crate::Runtime::new().block_on(async {
+            let callback = ArrivalCallback::default();
+            let source = create_node(&callback);
+            source.set_uplink_bandwidth(Some(Bandwidth::from_megabytes_per_second(1)));
+
+            for _ in 0..3 {
+                Node::connect(
+                    source.clone(),
+                    create_node(&callback),
+                    Duration::from_millis(10),
+                    Box::new(NoopLinkCallback),
+                );
+            }
+
+            source.broadcast(LargeMessage, None);
+            crate::time::sleep(Duration::from_seconds(4)).await;
+
+            // Each copy has to wait for the previous one to leave the node
+            let arrivals: Vec<_> = callback.arrivals.borrow().iter().map(|(_, t)| *t).collect();
+            assert_eq!(arrivals, vec![1010, 2010, 3010]);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Uplink bandwidth must not be zero")]
+    fn zero_uplink_bandwidth() {
+        crate::Runtime::new().block_on(async {
+            let node = create_node(&ArrivalCallback::default());
+            node.set_uplink_bandwidth(Some(Bandwidth::from_bits_per_second(0)));
+        });
+    }
+
+    #[test]
+    fn routing_tables_per_runtime() {
+        use crate::network::routing::{topology_changed, RoutingTable};
+
+        let (runtime1, runtime2) = (crate::Runtime::new(), crate::Runtime::new());
+
+        let table = {
+            let _ctx = runtime1.with_context();
+            RoutingTable::compute(&create_node(&ArrivalCallback::default()))
+        };
+
+        // Changes in another runtime do not affect this one
+        {
+            let _ctx = runtime2.with_context();
+            topology_changed();
+        }
+
+        let _ctx = runtime1.with_context();
+        assert!(table.is_current());
+        topology_changed();
+        assert!(!table.is_current());
+    }
+
+    #[test]
+    fn multi_hop() {
+        crate::Runtime::new().block_on(async {
+            let callbacks: Vec<_> = (0..5).map(|_| ArrivalCallback::default()).collect();
+            let nodes: Vec<_> = callbacks.iter().map(create_node).collect();
+            let ids: Vec<_> = nodes.iter().map(|node| node.get_identifier()).collect();
+
+            // A fast path (0-1-2-3), a slow shortcut (0-3), and a node without links (4)
+            let mut links = vec![];
+            for (first, second, latency) in [(0, 1, 10), (1, 2, 10), (2, 3, 10), (0, 3, 100)] {
+                links.push(Node::connect(
+                    nodes[first].clone(),
+                    nodes[second].clone(),
+                    Duration::from_millis(latency),
+                    Box::new(NoopLinkCallback),
+                ));
+            }
+
+            assert_eq!(nodes[0].get_next_hop(&ids[3]), Some(ids[1]));
+            assert!(nodes[0].send_to_any(&ids[3], LargeMessage));
+            assert!(!nodes[0].send_to_any(&ids[4], LargeMessage));
+
+            crate::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(*callbacks[3].arrivals.borrow(), vec![(ids[0], 30)]);
+            assert_eq!(callbacks[1].forwarded.get(), 1);
+            assert_eq!(callbacks[2].forwarded.get(), 1);
+
+            // Routes change with the topology
+            links[1].set_down(InFlight::Drop);
+            assert_eq!(nodes[0].get_next_hop(&ids[3]), Some(ids[3]));
+            assert_eq!(nodes[1].get_next_hop(&ids[2]), Some(ids[0]));
+
+            links[1].set_up();
+            assert_eq!(nodes[0].get_next_hop(&ids[3]), Some(ids[1]));
+        });
+    }
+}
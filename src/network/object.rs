@@ -1,19 +1,136 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ObjectId(u64);
+use std::cell::Cell;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::parse::ParseError;
 
 pub trait Object {
-    fn get_identifier(&self) -> ObjectId;
+    type Identifier;
+
+    fn get_identifier(&self) -> Self::Identifier;
     fn destroy(&self) {}
 }
 
-impl ObjectId {
-    pub fn random() -> Self {
-        Self(rand::random())
+/// Defines an identifier that is displayed as, e.g., "node#3"
+macro_rules! identifier {
+    ($name:ident, $prefix:literal) => {
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(u64);
+
+        impl $name {
+            pub fn as_u64(&self) -> u64 {
+                self.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+                write!(fmt, concat!($prefix, "#{}"), self.0)
+            }
+        }
+
+        /// Parses identifiers in the format they are displayed in
+        ///
+        /// The prefix is optional, e.g., "#3" and "3" are accepted as well
+        impl FromStr for $name {
+            type Err = ParseError;
+
+            fn from_str(input: &str) -> Result<Self, ParseError> {
+                let input = input.trim();
+                let number = input.strip_prefix($prefix).unwrap_or(input);
+                let number = number.strip_prefix('#').unwrap_or(number);
+
+                number.parse().map(Self).map_err(|_| {
+                    ParseError::new(format!(
+                        concat!("Invalid ", $prefix, " identifier \"{}\""),
+                        input
+                    ))
+                })
+            }
+        }
+    };
+}
+
+identifier!(NodeId, "node");
+identifier!(LinkId, "link");
+
+/// Hands out identifiers in the order objects are created
+///
+/// Every runtime has its own allocator, so that identifiers are the same in every run.
+#[derive(Default)]
+pub(crate) struct IdAllocator {
+    next_node_id: Cell<u64>,
+    next_link_id: Cell<u64>,
+}
+
+impl IdAllocator {
+    fn next(counter: &Cell<u64>) -> u64 {
+        let identifier = counter.get();
+        counter.set(identifier + 1);
+        identifier
     }
 }
 
-impl std::fmt::Display for ObjectId {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(fmt, "#{:x}", self.0)
+impl NodeId {
+    /// Get the next identifier from the current runtime
+    pub(super) fn allocate() -> Self {
+        crate::CONTEXT.with(|hdl| {
+            let hdl = hdl.borrow();
+            let hdl = hdl.as_ref().expect("Not in an asim context!");
+            Self(IdAllocator::next(&hdl.get_object_ids().next_node_id))
+        })
+    }
+}
+
+impl LinkId {
+    /// Get the next identifier from the current runtime
+    pub(super) fn allocate() -> Self {
+        crate::CONTEXT.with(|hdl| {
+            let hdl = hdl.borrow();
+            let hdl = hdl.as_ref().expect("Not in an asim context!");
+            Self(IdAllocator::next(&hdl.get_object_ids().next_link_id))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinkId, NodeId};
+
+    #[test]
+    fn parse_and_display() {
+        let node_id: NodeId = "node#12".parse().unwrap();
+        assert_eq!(node_id.as_u64(), 12);
+        assert_eq!(node_id.to_string(), "node#12");
+        assert_eq!("#12".parse(), Ok(node_id));
+        assert_eq!("12".parse(), Ok(node_id));
+
+        assert_eq!("link#3".parse::<LinkId>().unwrap().to_string(), "link#3");
+        assert!("link#3".parse::<NodeId>().is_err());
+        assert!("node#".parse::<NodeId>().is_err());
+    }
+
+    #[test]
+    fn sequential() {
+        let ids = |runtime: crate::Runtime| {
+            let _ctx = runtime.with_context();
+            (0..3).map(|_| NodeId::allocate()).collect::<Vec<_>>()
+        };
+
+        let first = ids(crate::Runtime::new());
+        assert_eq!(
+            first,
+            ["node#0", "node#1", "node#2"].map(|id| id.parse().unwrap())
+        );
+
+        // Every runtime starts from zero
+        assert_eq!(ids(crate::Runtime::new()), first);
+
+        let _ctx = crate::Runtime::new().with_context();
+        assert_eq!(LinkId::allocate().as_u64(), 0);
+        assert_eq!(NodeId::allocate().as_u64(), 0);
     }
 }
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::network::{InFlight, Link, LinkId, NetworkMessage, Node, NodeData, Object};
+
+/// Splits nodes into groups that cannot reach each other, until healed
+///
+/// All links between nodes of different groups go down. Links that involve nodes
+/// outside of any group, or that were already down, are not affected.
+pub struct Partition<Message: NetworkMessage, Data: NodeData> {
+    links: Vec<Rc<Link<Message, Data>>>,
+}
+
+impl<Message: NetworkMessage, Data: NodeData> Partition<Message, Data> {
+    /// Take down all links between the groups
+    ///
+    /// `in_flight` decides what happens to messages on these links (see [`Link::set_down`]).
+    pub fn new(groups: &[&[Rc<Node<Message, Data>>]], in_flight: InFlight) -> Self {
+        let mut group_of = HashMap::new();
+
+        for (idx, group) in groups.iter().enumerate() {
+            for node in group.iter() {
+                let prev = group_of.insert(node.get_identifier(), idx);
+                assert!(
+                    prev.is_none(),
+                    "Node {} is in more than one group",
+                    node.get_identifier()
+                );
+            }
+        }
+
+        let mut links: HashMap<LinkId, Rc<Link<Message, Data>>> = HashMap::new();
+
+        for node in groups.iter().flat_map(|group| group.iter()) {
+            for link in node.get_links() {
+                let (node1, node2) = link.get_nodes();
+                let group1 = group_of.get(&node1.get_identifier());
+                let group2 = group_of.get(&node2.get_identifier());
+
+                if let (Some(group1), Some(group2)) = (group1, group2) {
+                    if group1 != group2 && link.is_up() {
+                        links.insert(link.get_identifier(), link);
+                    }
+                }
+            }
+        }
+
+        // Sort, so that callbacks fire in a deterministic order
+        let mut links: Vec<_> = links.into_values().collect();
+        links.sort_by_key(|link| link.get_identifier());
+
+        log::debug!(
+            "Partitioning {} groups by taking down {} links",
+            groups.len(),
+            links.len()
+        );
+
+        for link in links.iter() {
+            link.set_down(in_flight);
+        }
+
+        Self { links }
+    }
+
+    /// The links this partition took down
+    pub fn get_links(&self) -> &[Rc<Link<Message, Data>>] {
+        &self.links
+    }
+
+    /// Bring all links back up
+    pub fn heal(self) {
+        for link in self.links {
+            link.set_up();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{
+        Bandwidth, DummyLinkCallback, DummyNodeCallback, DummyNodeData, InFlight, Node,
+    };
+    use crate::time::Duration;
+
+    use super::Partition;
+
+    #[test]
+    fn partition_and_heal() {
+        crate::Runtime::new().block_on(async {
+            let nodes: Vec<_> = (0..3)
+                .map(|_| {
+                    Node::new(
+                        Bandwidth::from_megabits_per_second(100),
+                        DummyNodeData::default(),
+                        Box::new(DummyNodeCallback::default()),
+                    )
+                })
+                .collect();
+
+            let mut links = vec![];
+            for (first, second) in [(0, 1), (0, 2), (1, 2)] {
+                links.push(Node::connect(
+                    nodes[first].clone(),
+                    nodes[second].clone(),
+                    Duration::from_millis(1),
+                    Box::new(DummyLinkCallback::default()),
+                ));
+            }
+
+            let partition = Partition::new(&[&nodes[..1], &nodes[1..]], InFlight::Drop);
+            assert_eq!(partition.get_links().len(), 2);
+
+            let is_up: Vec<_> = links.iter().map(|link| link.is_up()).collect();
+            assert_eq!(is_up, vec![false, false, true]);
+
+            partition.heal();
+            assert!(links.iter().all(|link| link.is_up()));
+        });
+    }
+}
@@ -0,0 +1,337 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::network::routing::{get_distances, shortest_path};
+use crate::network::topology::Topology;
+use crate::network::{
+    Bandwidth, InFlight, Latency, Link, LinkCallback, LinkId, NetworkMessage, Node, NodeCallback,
+    NodeData, NodeId, Object, Partition,
+};
+use crate::time::Duration;
+
+/// Keeps track of all nodes of a simulated network, and the links between them
+///
+/// Links are not registered separately. Instead, the network contains every link
+/// of its nodes, no matter if it was created using [`Network::connect`] or [`Node::connect`].
+///
+/// Nodes are iterated in the order of their identifiers, i.e., in the order they were created.
+pub struct Network<Message: NetworkMessage, Data: NodeData> {
+    nodes: RefCell<BTreeMap<NodeId, Rc<Node<Message, Data>>>>,
+}
+
+impl<Message: NetworkMessage, Data: NodeData> Default for Network<Message, Data> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Message: NetworkMessage, Data: NodeData> Network<Message, Data> {
+    pub fn new() -> Self {
+        Self {
+            nodes: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Create a new node and add it to the network
+    ///
+    /// See [`Node::new`] for details.
+    pub fn create_node(
+        &self,
+        bandwidth: Bandwidth,
+        data: Data,
+        callback: Box<dyn NodeCallback<Message, Data>>,
+    ) -> Rc<Node<Message, Data>> {
+        let node = Node::new(bandwidth, data, callback);
+        self.add_node(node.clone());
+        node
+    }
+
+    /// Add an existing node to the network
+    pub fn add_node(&self, node: Rc<Node<Message, Data>>) {
+        let prev = self.nodes.borrow_mut().insert(node.get_identifier(), node);
+        assert!(prev.is_none(), "Node was already added to the network");
+    }
+
+    /// Add all nodes of a topology (e.g., one created with [`crate::network::topology::ring`])
+    pub fn add_topology(&self, topology: Topology<Message, Data>) {
+        for node in topology.nodes {
+            self.add_node(node);
+        }
+    }
+
+    /// Disconnect a node from all its peers and remove it from the network
+    pub fn remove_node(&self, node_id: &NodeId) -> Option<Rc<Node<Message, Data>>> {
+        let node = self.nodes.borrow_mut().remove(node_id)?;
+        node.disconnect_all();
+        Some(node)
+    }
+
+    /// Connect two nodes of the network
+    ///
+    /// See [`Node::connect`] for details.
+    pub fn connect(
+        &self,
+        node1: &NodeId,
+        node2: &NodeId,
+        latency: Latency,
+        callback: Box<dyn LinkCallback<Message, Data>>,
+    ) -> Rc<Link<Message, Data>> {
+        Node::connect(
+            self.expect_node(node1),
+            self.expect_node(node2),
+            latency,
+            callback,
+        )
+    }
+
+    pub fn get_node(&self, node_id: &NodeId) -> Option<Rc<Node<Message, Data>>> {
+        self.nodes.borrow().get(node_id).cloned()
+    }
+
+    /// The first node with the given name (see [`Node::set_name`])
+    pub fn get_node_by_name(&self, name: &str) -> Option<Rc<Node<Message, Data>>> {
+        self.nodes
+            .borrow()
+            .values()
+            .find(|node| node.get_name().as_deref() == Some(name))
+            .cloned()
+    }
+
+    pub fn get_link(&self, link_id: &LinkId) -> Option<Rc<Link<Message, Data>>> {
+        self.get_links()
+            .into_iter()
+            .find(|link| link.get_identifier() == *link_id)
+    }
+
+    /// All nodes, in the order they were created
+    pub fn get_nodes(&self) -> Vec<Rc<Node<Message, Data>>> {
+        self.nodes.borrow().values().cloned().collect()
+    }
+
+    /// All links between nodes of this network, in the order of their identifiers
+    pub fn get_links(&self) -> Vec<Rc<Link<Message, Data>>> {
+        let nodes = self.nodes.borrow();
+        let mut links = BTreeMap::new();
+
+        for node in nodes.values() {
+            for link in node.get_links() {
+                let (node1, node2) = link.get_nodes();
+
+                if nodes.contains_key(&node1.get_identifier())
+                    && nodes.contains_key(&node2.get_identifier())
+                {
+                    links.insert(link.get_identifier(), link);
+                }
+            }
+        }
+
+        links.into_values().collect()
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.borrow().len()
+    }
+
+    pub fn num_links(&self) -> usize {
+        self.get_links().len()
+    }
+
+    /// The nodes the given node is directly connected to (None if the node does not exist)
+    pub fn get_neighbors(&self, node_id: &NodeId) -> Option<Vec<NodeId>> {
+        Some(self.get_node(node_id)?.get_peers())
+    }
+
+    /// How many links the given node has (None if the node does not exist)
+    pub fn get_degree(&self, node_id: &NodeId) -> Option<usize> {
+        Some(self.get_node(node_id)?.num_peers())
+    }
+
+    /// The path with the lowest average latency between two nodes, and that latency
+    ///
+    /// The path includes both `source` and `destination`. Links that are down are avoided.
+    /// Returns None if there is no path.
+    pub fn shortest_path(
+        &self,
+        source: &NodeId,
+        destination: &NodeId,
+    ) -> Option<(Vec<NodeId>, Duration)> {
+        shortest_path(&*self.get_node(source)?, destination)
+    }
+
+    /// The largest average latency between any two nodes, when using the shortest path
+    ///
+    /// Links that are down are ignored. Returns None if the network is empty,
+    /// or if some nodes cannot reach each other.
+    pub fn diameter(&self) -> Option<Duration> {
+        let nodes = self.get_nodes();
+        let mut diameter = None;
+
+        for node in nodes.iter() {
+            let distances = get_distances(&**node);
+
+            for other in nodes.iter() {
+                let distance = *distances.get(&other.get_identifier())?;
+                diameter = Some(diameter.map_or(distance, |d: Duration| d.max(distance)));
+            }
+        }
+
+        diameter
+    }
+
+    /// Send a message from the given node to every other node, possibly over multiple hops
+    ///
+    /// See [`Node::send_to_any`] for details.
+    /// Returns how many nodes could be reached.
+    pub fn broadcast(&self, source: &NodeId, message: Message) -> usize {
+        let source = self.expect_node(source);
+
+        self.get_nodes()
+            .iter()
+            .filter(|node| node.get_identifier() != source.get_identifier())
+            .filter(|node| source.send_to_any(&node.get_identifier(), message.clone()))
+            .count()
+    }
+
+    /// Stop all nodes (see [`Node::stop`])
+    pub fn stop_all(&self) {
+        for node in self.get_nodes() {
+            node.stop();
+        }
+    }
+
+    /// Split the network into groups of nodes that cannot reach each other
+    ///
+    /// See [`Partition::new`] for details. Call [`Partition::heal`] to undo it.
+    pub fn partition(&self, groups: &[&[NodeId]], in_flight: InFlight) -> Partition<Message, Data> {
+        let groups: Vec<Vec<_>> = groups
+            .iter()
+            .map(|group| group.iter().map(|id| self.expect_node(id)).collect())
+            .collect();
+        let groups: Vec<&[_]> = groups.iter().map(|group| group.as_slice()).collect();
+
+        Partition::new(&groups, in_flight)
+    }
+
+    fn expect_node(&self, node_id: &NodeId) -> Rc<Node<Message, Data>> {
+        self.get_node(node_id)
+            .unwrap_or_else(|| panic!("No node {node_id} in the network"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::Network;
+    use crate::network::topology::{line, LinkSpec};
+    use crate::network::{
+        Bandwidth, DummyLinkCallback, DummyNetworkMessage, DummyNodeData, InFlight, Node,
+        NodeCallback, NodeId, Object,
+    };
+    use crate::time::Duration;
+
+    #[derive(Default)]
+    struct CountingCallback {
+        received: Cell<usize>,
+        stopped: Cell<bool>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl NodeCallback<DummyNetworkMessage, DummyNodeData> for CountingCallback {
+        async fn handle_message(
+            &self,
+            _node: &Rc<Node<DummyNetworkMessage, DummyNodeData>>,
+            _source: NodeId,
+            _message: DummyNetworkMessage,
+        ) {
+            self.received.set(self.received.get() + 1);
+        }
+
+        fn node_stopped(&self, _node: &Node<DummyNetworkMessage, DummyNodeData>) {
+            self.stopped.set(true);
+        }
+    }
+
+    fn create_node(_idx: usize) -> Rc<Node<DummyNetworkMessage, DummyNodeData>> {
+        Node::new(
+            Bandwidth::from_megabits_per_second(100),
+            DummyNodeData::default(),
+            Box::new(CountingCallback::default()),
+        )
+    }
+
+    #[test]
+    fn queries_and_bulk_operations() {
+        crate::Runtime::new().block_on(async {
+            let network = Network::new();
+            let links = LinkSpec::new(Duration::from_millis(2), || {
+                Box::new(DummyLinkCallback::default())
+            });
+
+            // 0 - 1 - 2 - 3, plus a shortcut from 0 to 3
+            let topology = line(4, create_node, &links);
+            let ids: Vec<_> = topology.nodes.iter().map(|n| n.get_identifier()).collect();
+            network.add_topology(topology);
+
+            let shortcut = network.connect(
+                &ids[0],
+                &ids[3],
+                Duration::from_millis(5),
+                Box::new(DummyLinkCallback::default()),
+            );
+
+            assert_eq!(network.num_nodes(), 4);
+            assert_eq!(network.num_links(), 4);
+            assert_eq!(network.get_degree(&ids[0]), Some(2));
+            assert_eq!(network.get_degree(&"node#999".parse().unwrap()), None);
+            assert!(network.get_link(&shortcut.get_identifier()).is_some());
+
+            assert_eq!(network.get_neighbors(&ids[0]), Some(vec![ids[1], ids[3]]));
+
+            network.get_node(&ids[2]).unwrap().set_name("server");
+            let node = network.get_node_by_name("server").unwrap();
+            assert_eq!(node.get_identifier(), ids[2]);
+            assert_eq!(node.to_string(), "server");
+            assert!(network.get_node_by_name("client").is_none());
+
+            assert_eq!(
+                network.shortest_path(&ids[0], &ids[3]),
+                Some((vec![ids[0], ids[3]], Duration::from_millis(5)))
+            );
+            assert_eq!(network.diameter(), Some(Duration::from_millis(5)));
+
+            let partition = network.partition(&[&ids[..2], &ids[2..]], InFlight::Drop);
+            assert_eq!(network.shortest_path(&ids[0], &ids[3]), None);
+            assert_eq!(network.diameter(), None);
+            assert_eq!(network.broadcast(&ids[0], DummyNetworkMessage {}), 1);
+
+            partition.heal();
+            assert_eq!(
+                network.shortest_path(&ids[0], &ids[2]),
+                Some((vec![ids[0], ids[1], ids[2]], Duration::from_millis(4)))
+            );
+            assert_eq!(network.broadcast(&ids[0], DummyNetworkMessage {}), 3);
+
+            crate::time::sleep(Duration::from_millis(10)).await;
+
+            let received: Vec<_> = network
+                .get_nodes()
+                .iter()
+                .map(|node| node.get_callback_as::<CountingCallback>().received.get())
+                .collect();
+            assert_eq!(received, vec![0, 2, 1, 1]);
+
+            network.stop_all();
+            assert!(network
+                .get_nodes()
+                .iter()
+                .all(|node| node.get_callback_as::<CountingCallback>().stopped.get()));
+
+            network.remove_node(&ids[3]);
+            assert_eq!(network.num_links(), 2);
+            assert_eq!(network.get_degree(&ids[0]), Some(1));
+        });
+    }
+}
@@ -0,0 +1,154 @@
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+
+use crate::network::{NetworkMessage, Node, NodeData, NodeId, Object};
+use crate::time::Duration;
+
+/// Invalidate all routing tables of the current runtime
+pub(super) fn topology_changed() {
+    with_topology_version(|version| version.set(version.get() + 1));
+}
+
+fn topology_version() -> u64 {
+    with_topology_version(|version| version.get())
+}
+
+fn with_topology_version<R>(func: impl FnOnce(&Cell<u64>) -> R) -> R {
+    crate::CONTEXT.with(|hdl| {
+        let hdl = hdl.borrow();
+        let hdl = hdl.as_ref().expect("Not in an asim context!");
+        func(hdl.get_topology_version())
+    })
+}
+
+/// Where a message came from and where it is going, possibly across multiple hops
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Route {
+    pub origin: NodeId,
+    pub destination: NodeId,
+}
+
+/// The next hop towards every node reachable from a node
+pub(super) struct RoutingTable {
+    version: u64,
+    next_hops: HashMap<NodeId, NodeId>,
+}
+
+impl RoutingTable {
+    /// Is this table still valid for the current topology?
+    pub fn is_current(&self) -> bool {
+        self.version == topology_version()
+    }
+
+    pub fn next_hop(&self, destination: &NodeId) -> Option<NodeId> {
+        self.next_hops.get(destination).copied()
+    }
+
+    /// Find the paths with the lowest expected latency from `source`
+    pub fn compute<Message: NetworkMessage, Data: NodeData>(source: &Node<Message, Data>) -> Self {
+        Self {
+            version: topology_version(),
+            next_hops: Search::run(source).next_hops,
+        }
+    }
+}
+
+/// The path with the lowest expected latency between two nodes, and its latency
+///
+/// The path starts with `source` and ends with `destination`.
+pub(super) fn shortest_path<Message: NetworkMessage, Data: NodeData>(
+    source: &Node<Message, Data>,
+    destination: &NodeId,
+) -> Option<(Vec<NodeId>, Duration)> {
+    let search = Search::run(source);
+    let distance = *search.distances.get(destination)?;
+
+    let mut path = vec![*destination];
+    while let Some(previous) = search.previous.get(path.last().unwrap()) {
+        path.push(*previous);
+    }
+    path.reverse();
+
+    Some((path, distance))
+}
+
+/// The expected latency to every node reachable from `source` (including itself)
+pub(super) fn get_distances<Message: NetworkMessage, Data: NodeData>(
+    source: &Node<Message, Data>,
+) -> HashMap<NodeId, Duration> {
+    Search::run(source).distances
+}
+
+/// The state of Dijkstra's algorithm
+struct Search<Message: NetworkMessage, Data: NodeData> {
+    next_hops: HashMap<NodeId, NodeId>,
+    /// The node before each node on its path
+    previous: HashMap<NodeId, NodeId>,
+    distances: HashMap<NodeId, Duration>,
+    nodes: HashMap<NodeId, Rc<Node<Message, Data>>>,
+    queue: BinaryHeap<Reverse<(Duration, NodeId)>>,
+}
+
+impl<Message: NetworkMessage, Data: NodeData> Search<Message, Data> {
+    /// Find the paths with the lowest expected latency from `source`
+    ///
+    /// Only links that are up are used. Ties are broken by node identifier,
+    /// so that routes are deterministic.
+    fn run(source: &Node<Message, Data>) -> Self {
+        let mut search = Self {
+            next_hops: HashMap::new(),
+            previous: HashMap::new(),
+            distances: HashMap::from([(source.get_identifier(), Duration::ZERO)]),
+            nodes: HashMap::new(),
+            queue: BinaryHeap::new(),
+        };
+
+        search.visit(source, Duration::ZERO, None);
+
+        while let Some(Reverse((distance, node_id))) = search.queue.pop() {
+            // Skip outdated entries
+            if search.distances[&node_id] < distance {
+                continue;
+            }
+
+            let first_hop = search.next_hops[&node_id];
+            let node = search.nodes[&node_id].clone();
+            search.visit(&node, distance, Some(first_hop));
+        }
+
+        search
+    }
+
+    /// Update the distances of all neighbors of `node`
+    fn visit(&mut self, node: &Node<Message, Data>, distance: Duration, first_hop: Option<NodeId>) {
+        for link in node.get_links() {
+            if !link.is_up() {
+                continue;
+            }
+
+            let (node1, node2) = link.get_nodes();
+            let peer = if node1.get_identifier() == node.get_identifier() {
+                node2
+            } else {
+                node1
+            };
+
+            let peer_id = peer.get_identifier();
+            let distance = distance + link.get_mean_latency();
+
+            if self
+                .distances
+                .get(&peer_id)
+                .is_none_or(|prev| distance < *prev)
+            {
+                self.distances.insert(peer_id, distance);
+                self.next_hops.insert(peer_id, first_hop.unwrap_or(peer_id));
+                self.previous.insert(peer_id, node.get_identifier());
+                self.nodes.insert(peer_id, peer.clone());
+                self.queue.push(Reverse((distance, peer_id)));
+            }
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::rc::Rc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{LinkSpec, Topology};
+use crate::network::latency::from_nanos_f64;
+use crate::network::{Bandwidth, Latency, NetworkMessage, Node, NodeData, Object};
+use crate::ParseError;
+
+/// A node of a [`TopologyDescription`]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeDescription {
+    /// Identifies the node within the description
+    pub name: String,
+    /// The bandwidth of the node's network interface, if known
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub bandwidth: Option<Bandwidth>,
+}
+
+/// A link of a [`TopologyDescription`]
+///
+/// Links are undirected, so `source` and `target` can be swapped.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkDescription {
+    pub source: String,
+    pub target: String,
+    /// Replaces the latency of the [`LinkSpec`], if set
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub latency: Option<Latency>,
+    /// Replaces the bandwidth of the [`LinkSpec`] in both directions, if set
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub bandwidth: Option<Bandwidth>,
+}
+
+/// The nodes and links of a network, independent of the simulation's message and data types
+///
+/// Use this to load topologies from files (e.g., from the Internet Topology Zoo)
+/// and turn them into nodes and links with [`Self::instantiate`],
+/// or to save the nodes and links of a simulation with [`Self::from_nodes`].
+///
+/// The JSON format looks as follows, where latencies and bandwidths
+/// can be written with units or as plain nanoseconds and bits per second:
+/// ```json
+/// {
+///   "nodes": [{ "name": "a", "bandwidth": "1Gbit/s" }, { "name": "b" }],
+///   "links": [{ "source": "a", "target": "b", "latency": "10ms", "bandwidth": "100Mbit/s" }]
+/// }
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TopologyDescription {
+    pub nodes: Vec<NodeDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub links: Vec<LinkDescription>,
+}
+
+impl TopologyDescription {
+    /// Load a topology from a file
+    ///
+    /// The format is picked based on the file extension: ".graphml" or ".xml" for GraphML,
+    /// ".dot" or ".gv" for Graphviz DOT, and ".json" for JSON.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|err| {
+            ParseError::new(format!("Failed to read \"{}\": {err}", path.display()))
+        })?;
+
+        match get_extension(path).as_deref() {
+            Some("graphml" | "xml") => Self::from_graphml(&content),
+            Some("dot" | "gv") => Self::from_dot(&content),
+            #[cfg(feature = "serde")]
+            Some("json") => Self::from_json(&content),
+            _ => Err(ParseError::new(format!(
+                "Unsupported topology format \"{}\"",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Parse a GraphML document
+    ///
+    /// Nodes are named after their "id" attribute. The following attributes are used:
+    /// * Nodes: "bandwidth" or "capacity"
+    /// * Links: "latency" or "delay", and "bandwidth", "capacity", or "LinkSpeedRaw"
+    ///
+    /// Latencies without a unit are in milliseconds, and bandwidths without a unit
+    /// are in bits per second. Links without a latency whose nodes have a "Latitude"
+    /// and "Longitude" (as in the Internet Topology Zoo) get the time light takes
+    /// through fiber along the great-circle distance, i.e., 5μs per kilometer.
+    pub fn from_graphml(content: &str) -> Result<Self, ParseError> {
+        super::graphml::parse(content)
+    }
+
+    /// Parse a Graphviz DOT graph
+    ///
+    /// Uses the same attributes as [`Self::from_graphml`], and respects default
+    /// node and edge attributes (e.g., `edge [latency="5ms"]`).
+    /// Subgraphs and ports are not supported.
+    pub fn from_dot(content: &str) -> Result<Self, ParseError> {
+        super::dot::parse(content)
+    }
+
+    /// Parse the JSON format shown in the description of this type
+    #[cfg(feature = "serde")]
+    pub fn from_json(content: &str) -> Result<Self, ParseError> {
+        let description: Self = serde_json::from_str(content)
+            .map_err(|err| ParseError::new(format!("Invalid topology: {err}")))?;
+        description.validate()
+    }
+
+    /// Describe the given nodes and the links between them
+    ///
+    /// Nodes keep their name (see [`Node::set_name`]) if it is unique,
+    /// and are named after their identifier otherwise.
+    /// Links to nodes outside of `nodes` are left out.
+    /// If the two directions of a link have different bandwidths, the lower one is used.
+    pub fn from_nodes<Message: NetworkMessage, Data: NodeData>(
+        nodes: &[Rc<Node<Message, Data>>],
+    ) -> Self {
+        let mut name_counts = HashMap::new();
+        for name in nodes.iter().filter_map(|node| node.get_name()) {
+            *name_counts.entry(name).or_insert(0) += 1;
+        }
+
+        let names: HashMap<_, _> = nodes
+            .iter()
+            .map(|node| {
+                let name = match node.get_name() {
+                    Some(name) if name_counts[&name] == 1 => name,
+                    _ => node.get_identifier().to_string(),
+                };
+                (node.get_identifier(), name)
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut links = vec![];
+
+        for node in nodes {
+            for link in node.get_links() {
+                let (node1, node2) = link.get_nodes();
+
+                if !names.contains_key(&node1.get_identifier())
+                    || !names.contains_key(&node2.get_identifier())
+                    || !seen.insert(link.get_identifier())
+                {
+                    continue;
+                }
+
+                let bandwidth = match (
+                    link.get_bandwidth(&node1.get_identifier()),
+                    link.get_bandwidth(&node2.get_identifier()),
+                ) {
+                    (Some(first), Some(second)) => Some(
+                        if first.into_bits_per_second() <= second.into_bits_per_second() {
+                            first
+                        } else {
+                            second
+                        },
+                    ),
+                    (bandwidth, None) | (None, bandwidth) => bandwidth,
+                };
+
+                links.push(LinkDescription {
+                    source: names[&node1.get_identifier()].clone(),
+                    target: names[&node2.get_identifier()].clone(),
+                    latency: Some(link.get_mean_latency()),
+                    bandwidth,
+                });
+            }
+        }
+
+        let nodes = nodes
+            .iter()
+            .map(|node| NodeDescription {
+                name: names[&node.get_identifier()].clone(),
+                bandwidth: Some(node.get_downlink_bandwidth()),
+            })
+            .collect();
+
+        Self { nodes, links }
+    }
+
+    /// Write the topology as a Graphviz DOT graph
+    ///
+    /// Links are labeled with their latency, so that they show up when rendered.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("graph {\n");
+
+        for node in &self.nodes {
+            result += &format!("    {}", quote(&node.name));
+            if let Some(bandwidth) = node.bandwidth {
+                result += &format!(" [bandwidth=\"{bandwidth}\"]");
+            }
+            result += ";\n";
+        }
+
+        for link in &self.links {
+            let mut attributes = vec![];
+            if let Some(latency) = link.latency {
+                attributes.push(format!("latency=\"{latency}\", label=\"{latency}\""));
+            }
+            if let Some(bandwidth) = link.bandwidth {
+                attributes.push(format!("bandwidth=\"{bandwidth}\""));
+            }
+
+            result += &format!("    {} -- {}", quote(&link.source), quote(&link.target));
+            if !attributes.is_empty() {
+                result += &format!(" [{}]", attributes.join(", "));
+            }
+            result += ";\n";
+        }
+
+        result += "}\n";
+        result
+    }
+
+    /// Write the topology in the JSON format shown in the description of this type
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize topology")
+    }
+
+    /// Save the topology to a file
+    ///
+    /// The format is picked based on the file extension: ".dot" or ".gv" for Graphviz DOT,
+    /// and ".json" for JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+
+        let content = match get_extension(path).as_deref() {
+            Some("dot" | "gv") => self.to_dot(),
+            #[cfg(feature = "serde")]
+            Some("json") => self.to_json(),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Unsupported topology format \"{}\"", path.display()),
+                ));
+            }
+        };
+
+        std::fs::write(path, content)
+    }
+
+    /// Create the described nodes and links
+    ///
+    /// Nodes are created in the order they are listed, using `create_node`,
+    /// and named after their description unless `create_node` names them.
+    /// Links use the latency and bandwidth of their description, if set,
+    /// and those of `links` otherwise.
+    pub fn instantiate<Message: NetworkMessage, Data: NodeData>(
+        &self,
+        mut create_node: impl FnMut(&NodeDescription) -> Rc<Node<Message, Data>>,
+        links: &LinkSpec<Message, Data>,
+    ) -> Topology<Message, Data> {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|description| {
+                let node = create_node(description);
+                if node.get_name().is_none() {
+                    node.set_name(description.name.clone());
+                }
+                node
+            })
+            .collect();
+        let by_name: HashMap<_, _> = self
+            .nodes
+            .iter()
+            .zip(nodes.iter())
+            .map(|(description, node)| (description.name.as_str(), node))
+            .collect();
+
+        let links = self
+            .links
+            .iter()
+            .map(|link| {
+                let get_node = |name: &str| {
+                    *by_name
+                        .get(name)
+                        .unwrap_or_else(|| panic!("No node named \"{name}\""))
+                };
+
+                links.connect(
+                    get_node(&link.source),
+                    get_node(&link.target),
+                    link.latency,
+                    link.bandwidth,
+                )
+            })
+            .collect();
+
+        Topology { nodes, links }
+    }
+
+    /// Ensure all links connect existing nodes
+    ///
+    /// Links that connect a node to itself, or nodes that are already connected,
+    /// are dropped, because the simulation only supports one link between two nodes.
+    pub(super) fn validate(mut self) -> Result<Self, ParseError> {
+        let mut names = HashSet::new();
+        for node in &self.nodes {
+            if !names.insert(node.name.as_str()) {
+                return Err(ParseError::new(format!("Duplicate node \"{}\"", node.name)));
+            }
+        }
+
+        // Sending over a zero bandwidth would never finish
+        let is_zero = |bandwidth: Option<Bandwidth>| {
+            bandwidth.is_some_and(|bandwidth| bandwidth.into_bits_per_second() == 0)
+        };
+
+        if let Some(node) = self.nodes.iter().find(|node| is_zero(node.bandwidth)) {
+            return Err(ParseError::new(format!(
+                "Zero bandwidth for node \"{}\"",
+                node.name
+            )));
+        }
+
+        if let Some(link) = self.links.iter().find(|link| is_zero(link.bandwidth)) {
+            return Err(ParseError::new(format!(
+                "Zero bandwidth for link \"{}\"-\"{}\"",
+                link.source, link.target
+            )));
+        }
+
+        for link in &self.links {
+            for name in [&link.source, &link.target] {
+                if !names.contains(name.as_str()) {
+                    return Err(ParseError::new(format!("Link to unknown node \"{name}\"")));
+                }
+            }
+        }
+
+        let mut connected = HashSet::new();
+        self.links.retain(|link| {
+            if link.source == link.target {
+                log::warn!("Ignoring link from \"{}\" to itself", link.source);
+                return false;
+            }
+
+            let pair = if link.source < link.target {
+                (link.source.clone(), link.target.clone())
+            } else {
+                (link.target.clone(), link.source.clone())
+            };
+
+            if !connected.insert(pair) {
+                log::warn!(
+                    "Ignoring duplicate link between \"{}\" and \"{}\"",
+                    link.source,
+                    link.target
+                );
+                return false;
+            }
+
+            true
+        });
+
+        Ok(self)
+    }
+}
+
+fn get_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Put a name in quotes, as required by DOT
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Parse a latency, where plain numbers are in milliseconds
+pub(super) fn parse_latency(value: &str) -> Result<Latency, ParseError> {
+    let value = value.trim();
+
+    match value.parse::<f64>() {
+        Ok(millis) if millis.is_finite() && millis >= 0.0 => Ok(from_nanos_f64(millis * 1e6)),
+        Ok(_) => Err(ParseError::new(format!("Invalid latency \"{value}\""))),
+        Err(_) => value.parse(),
+    }
+}
+
+/// Parse a bandwidth, where plain numbers are in bits per second
+///
+/// Some datasets use zero for unknown capacities, which is rejected
+/// as nodes and links need a positive bandwidth.
+pub(super) fn parse_bandwidth(value: &str) -> Result<Bandwidth, ParseError> {
+    let value = value.trim();
+
+    let bandwidth = match value.parse::<f64>() {
+        Ok(bps) if bps.is_finite() && bps >= 0.0 => {
+            Bandwidth::from_bits_per_second(bps.round() as u64)
+        }
+        Ok(_) => return Err(ParseError::new(format!("Invalid bandwidth \"{value}\""))),
+        Err(_) => value.parse()?,
+    };
+
+    if bandwidth.into_bits_per_second() == 0 {
+        return Err(ParseError::new(format!("Zero bandwidth \"{value}\"")));
+    }
+
+    Ok(bandwidth)
+}
+
+/// Find the attribute with one of the given names (ignoring case)
+pub(super) fn find_attribute<'a>(
+    attributes: &'a HashMap<String, String>,
+    names: &[&str],
+) -> Option<&'a str> {
+    names.iter().find_map(|name| {
+        attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    })
+}
+
+/// Build a node from its attributes, as used by GraphML and DOT
+pub(super) fn parse_node(
+    name: String,
+    attributes: &HashMap<String, String>,
+) -> Result<NodeDescription, ParseError> {
+    let bandwidth = find_attribute(attributes, &["bandwidth", "capacity"])
+        .map(parse_bandwidth)
+        .transpose()
+        .map_err(|err| ParseError::new(format!("{err} for node \"{name}\"")))?;
+
+    Ok(NodeDescription { name, bandwidth })
+}
+
+/// Build a link from its attributes, as used by GraphML and DOT
+pub(super) fn parse_link(
+    source: String,
+    target: String,
+    attributes: &HashMap<String, String>,
+) -> Result<LinkDescription, ParseError> {
+    let error =
+        |err: ParseError| ParseError::new(format!("{err} for link \"{source}\"-\"{target}\""));
+
+    let latency = find_attribute(attributes, &["latency", "delay"])
+        .map(parse_latency)
+        .transpose()
+        .map_err(error)?;
+    let bandwidth = find_attribute(attributes, &["bandwidth", "capacity", "LinkSpeedRaw"])
+        .map(parse_bandwidth)
+        .transpose()
+        .map_err(error)?;
+
+    Ok(LinkDescription {
+        source,
+        target,
+        latency,
+        bandwidth,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{
+        DummyLinkCallback, DummyNetworkMessage, DummyNodeCallback, DummyNodeData,
+    };
+    use crate::time::Duration;
+
+    fn example() -> TopologyDescription {
+        TopologyDescription {
+            nodes: vec![
+                NodeDescription {
+                    name: "a".to_string(),
+                    bandwidth: Some(Bandwidth::from_gigabits_per_second(1)),
+                },
+                NodeDescription {
+                    name: "b \"quoted\"".to_string(),
+                    bandwidth: None,
+                },
+            ],
+            links: vec![LinkDescription {
+                source: "a".to_string(),
+                target: "b \"quoted\"".to_string(),
+                latency: Some(Duration::from_millis(10)),
+                bandwidth: Some(Bandwidth::from_megabits_per_second(100)),
+            }],
+        }
+    }
+
+    #[test]
+    fn dot_round_trip() {
+        let description = example();
+        let dot = description.to_dot();
+
+        assert!(dot.contains("\"a\" -- \"b \\\"quoted\\\"\""));
+        assert_eq!(TopologyDescription::from_dot(&dot).unwrap(), description);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let description = example();
+        let json = description.to_json();
+
+        assert!(json.contains("\"latency\": \"10ms\""));
+        assert_eq!(TopologyDescription::from_json(&json).unwrap(), description);
+
+        let result = TopologyDescription::from_json(
+            r#"{ "nodes": [{ "name": "a" }], "links": [{ "source": "a", "target": "c" }] }"#,
+        );
+        assert!(result.is_err());
+
+        let result = TopologyDescription::from_json(
+            r#"{ "nodes": [{ "name": "a", "bandwidth": 0 }], "links": [] }"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn zero_bandwidth() {
+        for value in ["0", "0.2", "0bit/s", "0Gbps"] {
+            assert!(parse_bandwidth(value).is_err(), "{value}");
+        }
+        assert_eq!(parse_bandwidth("1"), Ok(Bandwidth::from_bits_per_second(1)));
+
+        assert!(TopologyDescription::from_dot("graph { a -- b [capacity=0] }").is_err());
+        assert!(TopologyDescription::from_dot("graph { a [bandwidth=\"0Mbps\"] }").is_err());
+    }
+
+    #[test]
+    fn instantiate_and_export() {
+        crate::Runtime::new().block_on(async {
+            let description = TopologyDescription {
+                nodes: example().nodes,
+                links: vec![
+                    example().links[0].clone(),
+                    LinkDescription {
+                        source: "b \"quoted\"".to_string(),
+                        target: "a".to_string(),
+                        latency: None,
+                        bandwidth: None,
+                    },
+                ],
+            }
+            .validate()
+            .unwrap();
+
+            // The second link connects the same nodes
+            assert_eq!(description.links.len(), 1);
+
+            let links = LinkSpec::new(Duration::from_millis(5), || {
+                Box::new(DummyLinkCallback::default())
+            });
+
+            let topology = description.instantiate(
+                |node| {
+                    Node::<DummyNetworkMessage, DummyNodeData>::new(
+                        node.bandwidth
+                            .unwrap_or(Bandwidth::from_megabits_per_second(10)),
+                        DummyNodeData::default(),
+                        Box::new(DummyNodeCallback::default()),
+                    )
+                },
+                &links,
+            );
+
+            let link = &topology.links[0];
+            assert_eq!(link.get_mean_latency(), Duration::from_millis(10));
+            assert_eq!(
+                link.get_bandwidth(&topology.nodes[1].get_identifier()),
+                Some(Bandwidth::from_megabits_per_second(100))
+            );
+
+            assert_eq!(topology.nodes[0].to_string(), "a");
+
+            let exported = TopologyDescription::from_nodes(&topology.nodes);
+            assert_eq!(exported.nodes.len(), 2);
+            assert_eq!(exported.nodes[1].name, "b \"quoted\"");
+            assert_eq!(
+                exported.nodes[0].bandwidth,
+                Some(Bandwidth::from_gigabits_per_second(1))
+            );
+            assert_eq!(exported.links.len(), 1);
+            assert_eq!(exported.links[0].latency, Some(Duration::from_millis(10)));
+
+            // Links to nodes that are left out are not exported
+            let exported = TopologyDescription::from_nodes(&topology.nodes[..1]);
+            assert!(exported.links.is_empty());
+
+            // Names that are not unique are replaced by identifiers
+            topology.nodes[1].set_name("a");
+            let exported = TopologyDescription::from_nodes(&topology.nodes);
+            assert_eq!(exported.links[0].source, "node#0");
+            assert_eq!(exported.links[0].target, "node#1");
+        });
+    }
+}
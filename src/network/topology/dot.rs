@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use super::description::{parse_link, parse_node};
+use super::TopologyDescription;
+use crate::ParseError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A name, number, or quoted string
+    Id(String),
+    /// "--" or "->"
+    EdgeOp,
+    Symbol(char),
+}
+
+/// Split DOT source into tokens, along with the line they are on
+fn tokenize(content: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        let at_line_start = line_start;
+        line_start = false;
+
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = true;
+            }
+            c if c.is_whitespace() => {
+                line_start = at_line_start;
+            }
+            // Preprocessor output
+            '#' if at_line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        line_start = true;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        line_start = true;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            prev = c;
+                        }
+                        None => {
+                            return Err(ParseError::new(format!(
+                                "Unterminated comment on line {line}"
+                            )));
+                        }
+                    }
+                }
+            }
+            '-' if matches!(chars.peek(), Some('-' | '>')) => {
+                chars.next();
+                tokens.push((Token::EdgeOp, line));
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' => {
+                tokens.push((Token::Symbol(c), line));
+            }
+            '"' => {
+                let start = line;
+                let mut value = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => value.push(c),
+                            // Line continuation
+                            Some('\n') => line += 1,
+                            Some(c) => {
+                                value.push('\\');
+                                value.push(c);
+                            }
+                            None => {}
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            value.push(c);
+                        }
+                        None => {
+                            return Err(ParseError::new(format!(
+                                "Unterminated string on line {start}"
+                            )));
+                        }
+                    }
+                }
+
+                tokens.push((Token::Id(value), start));
+            }
+            // Names and (possibly negative) numbers
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut value = String::from(c);
+
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '.' {
+                        value.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                tokens.push((Token::Id(value), line));
+            }
+            c => {
+                return Err(ParseError::new(format!(
+                    "Unsupported character '{c}' on line {line}"
+                )));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    description: TopologyDescription,
+    /// Maps node names to their position in the description
+    node_indices: HashMap<String, usize>,
+    node_defaults: HashMap<String, String>,
+    edge_defaults: HashMap<String, String>,
+    /// The attributes of each node, applied once the whole graph has been read
+    node_attributes: Vec<HashMap<String, String>>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(token, _)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        match self.tokens.get(self.position.saturating_sub(1)) {
+            Some((_, line)) => ParseError::new(format!("{message} on line {line}")),
+            None => ParseError::new(message),
+        }
+    }
+
+    fn expect_id(&mut self) -> Result<String, ParseError> {
+        match self.next() {
+            Some(Token::Id(id)) => Ok(id),
+            _ => Err(self.error("Expected an identifier")),
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), ParseError> {
+        match self.next() {
+            Some(Token::Symbol(c)) if c == symbol => Ok(()),
+            _ => Err(self.error(&format!("Expected '{symbol}'"))),
+        }
+    }
+
+    fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+        matches!(token, Some(Token::Id(id)) if id.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Parse one or more attribute lists, e.g., `[a=1, b=2][c=3]`
+    fn parse_attributes(&mut self) -> Result<HashMap<String, String>, ParseError> {
+        let mut attributes = HashMap::new();
+
+        while self.peek() == Some(&Token::Symbol('[')) {
+            self.next();
+
+            loop {
+                match self.peek() {
+                    Some(Token::Symbol(']')) => {
+                        self.next();
+                        break;
+                    }
+                    Some(Token::Symbol(',' | ';')) => {
+                        self.next();
+                    }
+                    _ => {
+                        let key = self.expect_id()?;
+                        self.expect_symbol('=')?;
+                        let value = self.expect_id()?;
+                        attributes.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Get the index of a node, creating it if it does not exist yet
+    fn get_node(&mut self, name: String) -> usize {
+        if let Some(idx) = self.node_indices.get(&name) {
+            return *idx;
+        }
+
+        let idx = self.node_attributes.len();
+        self.node_attributes.push(self.node_defaults.clone());
+        self.node_indices.insert(name.clone(), idx);
+        self.description.nodes.push(super::NodeDescription {
+            name,
+            bandwidth: None,
+        });
+
+        idx
+    }
+
+    fn parse_statement(&mut self) -> Result<(), ParseError> {
+        let token = self.peek().cloned();
+
+        if Self::is_keyword(token.as_ref(), "subgraph") || token == Some(Token::Symbol('{')) {
+            return Err(self.error("Subgraphs are not supported"));
+        }
+
+        let id = self.expect_id()?;
+
+        if id.eq_ignore_ascii_case("graph") {
+            self.parse_attributes()?;
+        } else if id.eq_ignore_ascii_case("node") {
+            let attributes = self.parse_attributes()?;
+            self.node_defaults.extend(attributes);
+        } else if id.eq_ignore_ascii_case("edge") {
+            let attributes = self.parse_attributes()?;
+            self.edge_defaults.extend(attributes);
+        } else if self.peek() == Some(&Token::Symbol('=')) {
+            // Graph attribute
+            self.next();
+            self.expect_id()?;
+        } else {
+            let mut names = vec![id];
+            while self.peek() == Some(&Token::EdgeOp) {
+                self.next();
+                names.push(self.expect_id()?);
+            }
+
+            let attributes = self.parse_attributes()?;
+            let indices: Vec<_> = names.into_iter().map(|name| self.get_node(name)).collect();
+
+            if indices.len() == 1 {
+                self.node_attributes[indices[0]].extend(attributes);
+            } else {
+                let mut edge_attributes = self.edge_defaults.clone();
+                edge_attributes.extend(attributes);
+
+                for pair in indices.windows(2) {
+                    let source = self.description.nodes[pair[0]].name.clone();
+                    let target = self.description.nodes[pair[1]].name.clone();
+                    let link = parse_link(source, target, &edge_attributes)?;
+                    self.description.links.push(link);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse(mut self) -> Result<TopologyDescription, ParseError> {
+        if Self::is_keyword(self.peek(), "strict") {
+            self.next();
+        }
+
+        let token = self.next();
+        if !Self::is_keyword(token.as_ref(), "graph")
+            && !Self::is_keyword(token.as_ref(), "digraph")
+        {
+            return Err(self.error("Expected \"graph\" or \"digraph\""));
+        }
+
+        // The graph's name is optional
+        if let Some(Token::Id(_)) = self.peek() {
+            self.next();
+        }
+
+        self.expect_symbol('{')?;
+
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('}')) => {
+                    self.next();
+                    break;
+                }
+                Some(Token::Symbol(';')) => {
+                    self.next();
+                }
+                Some(_) => self.parse_statement()?,
+                None => return Err(self.error("Expected '}'")),
+            }
+        }
+
+        if self.peek().is_some() {
+            self.next();
+            return Err(self.error("Unexpected content after the graph"));
+        }
+
+        for (node, attributes) in self
+            .description
+            .nodes
+            .iter_mut()
+            .zip(self.node_attributes.iter())
+        {
+            *node = parse_node(std::mem::take(&mut node.name), attributes)?;
+        }
+
+        self.description.validate()
+    }
+}
+
+pub(super) fn parse(content: &str) -> Result<TopologyDescription, ParseError> {
+    let parser = Parser {
+        tokens: tokenize(content)?,
+        position: 0,
+        description: TopologyDescription::default(),
+        node_indices: HashMap::new(),
+        node_defaults: HashMap::new(),
+        edge_defaults: HashMap::new(),
+        node_attributes: vec![],
+    };
+
+    parser.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::topology::TopologyDescription;
+    use crate::network::Bandwidth;
+    use crate::time::Duration;
+
+    #[test]
+    fn parse_dot() {
+        let content = r#"
+            # generated
+            strict digraph "test" {
+                rankdir=LR; // a graph attribute
+                node [bandwidth="1Gbps"];
+                edge [latency=2];
+
+                a -> b -> c [bandwidth=1000];
+                /* c uses the default latency
+                   and its own bandwidth */
+                c [bandwidth="10Mbit/s"]
+                edge [latency="5ms"]
+                "d e" -> a;
+            }
+        "#;
+
+        let description = TopologyDescription::from_dot(content).unwrap();
+        let names: Vec<_> = description.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c", "d e"]);
+
+        assert_eq!(
+            description.nodes[0].bandwidth,
+            Some(Bandwidth::from_gigabits_per_second(1))
+        );
+        assert_eq!(
+            description.nodes[2].bandwidth,
+            Some(Bandwidth::from_megabits_per_second(10))
+        );
+
+        let links: Vec<_> = description
+            .links
+            .iter()
+            .map(|l| (l.source.as_str(), l.target.as_str(), l.latency, l.bandwidth))
+            .collect();
+
+        assert_eq!(
+            links,
+            [
+                (
+                    "a",
+                    "b",
+                    Some(Duration::from_millis(2)),
+                    Some(Bandwidth::from_bits_per_second(1000))
+                ),
+                (
+                    "b",
+                    "c",
+                    Some(Duration::from_millis(2)),
+                    Some(Bandwidth::from_bits_per_second(1000))
+                ),
+                ("d e", "a", Some(Duration::from_millis(5)), None),
+            ]
+        );
+
+        assert!(TopologyDescription::from_dot("graph { a -- b").is_err());
+        assert!(TopologyDescription::from_dot("graph { subgraph { a } }").is_err());
+        assert!(TopologyDescription::from_dot("graph { a -- b [latency=fast] }").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use super::description::{find_attribute, parse_link, parse_node};
+use super::TopologyDescription;
+use crate::ParseError;
+
+/// How long light takes through one kilometer of fiber
+const FIBER_DELAY_PER_KM: f64 = 5_000.0;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A `<key>` element, which declares an attribute
+struct Key {
+    name: String,
+    default: Option<String>,
+}
+
+/// Collect the attributes of a node or edge, including those that use their default value
+fn get_attributes(element: roxmltree::Node, keys: &HashMap<&str, Key>) -> HashMap<String, String> {
+    let mut attributes: HashMap<String, String> = keys
+        .iter()
+        .filter(|(_, key)| key.default.is_some())
+        .map(|(_, key)| (key.name.clone(), key.default.clone().unwrap()))
+        .collect();
+
+    for data in element.children().filter(|c| c.has_tag_name("data")) {
+        let Some(key) = data.attribute("key") else {
+            continue;
+        };
+
+        // Empty values are treated like missing ones
+        let value = data.text().unwrap_or("").trim();
+        if value.is_empty() {
+            continue;
+        }
+
+        // Fall back to the identifier for undeclared keys
+        let name = keys.get(key).map_or(key, |key| key.name.as_str());
+        attributes.insert(name.to_string(), value.to_string());
+    }
+
+    attributes
+}
+
+/// The latitude and longitude of a node, in degrees
+fn get_location(attributes: &HashMap<String, String>) -> Option<(f64, f64)> {
+    let latitude = find_attribute(attributes, &["Latitude"])?.parse().ok()?;
+    let longitude = find_attribute(attributes, &["Longitude"])?.parse().ok()?;
+    Some((latitude, longitude))
+}
+
+/// The great-circle distance between two locations (using the haversine formula)
+fn distance_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let delta_lat = lat2 - lat1;
+    let delta_lon = (lon2 - lon1).to_radians();
+
+    let a =
+        (delta_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+pub(super) fn parse(content: &str) -> Result<TopologyDescription, ParseError> {
+    let document = roxmltree::Document::parse(content)
+        .map_err(|err| ParseError::new(format!("Invalid GraphML: {err}")))?;
+
+    let root = document.root_element();
+    if !root.has_tag_name("graphml") {
+        return Err(ParseError::new("Not a GraphML document"));
+    }
+
+    // Keys for nodes and for edges may share identifiers
+    let mut node_keys = HashMap::new();
+    let mut edge_keys = HashMap::new();
+
+    for element in root.children().filter(|c| c.has_tag_name("key")) {
+        let Some(identifier) = element.attribute("id") else {
+            return Err(ParseError::new("GraphML key without an identifier"));
+        };
+
+        let key = || Key {
+            name: element
+                .attribute("attr.name")
+                .unwrap_or(identifier)
+                .to_string(),
+            default: element
+                .children()
+                .find(|c| c.has_tag_name("default"))
+                .and_then(|c| c.text())
+                .map(|text| text.trim().to_string()),
+        };
+
+        match element.attribute("for").unwrap_or("all") {
+            "node" => {
+                node_keys.insert(identifier, key());
+            }
+            "edge" => {
+                edge_keys.insert(identifier, key());
+            }
+            "all" => {
+                node_keys.insert(identifier, key());
+                edge_keys.insert(identifier, key());
+            }
+            _ => {}
+        }
+    }
+
+    let Some(graph) = root.children().find(|c| c.has_tag_name("graph")) else {
+        return Err(ParseError::new("GraphML document without a graph"));
+    };
+
+    let mut description = TopologyDescription::default();
+    let mut locations = HashMap::new();
+
+    for element in graph.children().filter(|c| c.has_tag_name("node")) {
+        let Some(name) = element.attribute("id") else {
+            return Err(ParseError::new("GraphML node without an identifier"));
+        };
+
+        let attributes = get_attributes(element, &node_keys);
+        if let Some(location) = get_location(&attributes) {
+            locations.insert(name.to_string(), location);
+        }
+
+        description
+            .nodes
+            .push(parse_node(name.to_string(), &attributes)?);
+    }
+
+    for element in graph.children().filter(|c| c.has_tag_name("edge")) {
+        let (Some(source), Some(target)) =
+            (element.attribute("source"), element.attribute("target"))
+        else {
+            return Err(ParseError::new("GraphML edge without source or target"));
+        };
+
+        let attributes = get_attributes(element, &edge_keys);
+        let mut link = parse_link(source.to_string(), target.to_string(), &attributes)?;
+
+        if link.latency.is_none() {
+            if let (Some(first), Some(second)) = (locations.get(source), locations.get(target)) {
+                let delay = distance_km(*first, *second) * FIBER_DELAY_PER_KM;
+                link.latency = Some(crate::network::latency::from_nanos_f64(delay));
+            }
+        }
+
+        description.links.push(link);
+    }
+
+    description.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::topology::TopologyDescription;
+    use crate::network::Bandwidth;
+    use crate::time::Duration;
+
+    #[test]
+    fn topology_zoo() {
+        // Shortened from the Internet Topology Zoo
+        let content = r#"<?xml version="1.0" encoding="utf-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key attr.name="Longitude" attr.type="double" for="node" id="d32" />
+  <key attr.name="Latitude" attr.type="double" for="node" id="d29" />
+  <key attr.name="label" attr.type="string" for="node" id="d33" />
+  <key attr.name="LinkSpeedRaw" attr.type="double" for="edge" id="d37">
+    <default>1000000</default>
+  </key>
+  <key attr.name="latency" attr.type="string" for="edge" id="d38" />
+  <graph edgedefault="undirected">
+    <node id="0">
+      <data key="d29">52.37403</data>
+      <data key="d32">4.88969</data>
+      <data key="d33">Amsterdam</data>
+    </node>
+    <node id="1">
+      <data key="d29">51.50853</data>
+      <data key="d32">-0.12574</data>
+      <data key="d33">London</data>
+    </node>
+    <node id="2" />
+    <edge source="0" target="1">
+      <data key="d37">10000000000.0</data>
+      <data key="d38"></data>
+    </edge>
+    <edge source="1" target="2">
+      <data key="d38">3ms</data>
+    </edge>
+    <edge source="2" target="1" />
+  </graph>
+</graphml>"#;
+
+        let description = TopologyDescription::from_graphml(content).unwrap();
+        assert_eq!(description.nodes.len(), 3);
+        assert_eq!(description.nodes[0].name, "0");
+
+        // The duplicate edge is dropped
+        assert_eq!(description.links.len(), 2);
+
+        // About 358km between Amsterdam and London
+        let link = &description.links[0];
+        let latency = link.latency.unwrap().as_millis_f64();
+        assert!((latency - 1.79).abs() < 0.01, "{latency}");
+        assert_eq!(
+            link.bandwidth,
+            Some(Bandwidth::from_bits_per_second(10_000_000_000))
+        );
+
+        let link = &description.links[1];
+        assert_eq!(link.latency, Some(Duration::from_millis(3)));
+        // Uses the default value
+        assert_eq!(
+            link.bandwidth,
+            Some(Bandwidth::from_bits_per_second(1_000_000))
+        );
+
+        assert!(TopologyDescription::from_graphml("<graphml><graph>").is_err());
+        assert!(TopologyDescription::from_graphml("<graph></graph>").is_err());
+    }
+}
@@ -0,0 +1,509 @@
+/// Generators for common network shapes
+///
+/// Every generator takes a function that creates the node with a given index,
+/// and a [`LinkSpec`] that describes the links. Randomized generators draw from
+/// the runtime's seeded random number generator (see [`crate::random`]),
+/// so the same seed yields the same topology.
+///
+/// Topologies can also be loaded from GraphML, Graphviz DOT, or JSON files,
+/// and saved to DOT or JSON (see [`TopologyDescription`]).
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use crate::network::{
+    Bandwidth, ConstantLatency, Latency, LatencyModel, Link, LinkCallback, NetworkMessage, Node,
+    NodeData, Object,
+};
+use crate::random::{with_rng, Rng};
+
+mod description;
+pub use description::{LinkDescription, NodeDescription, TopologyDescription};
+
+mod dot;
+mod graphml;
+
+/// Describes the links of a generated topology
+pub struct LinkSpec<Message: NetworkMessage, Data: NodeData> {
+    /// Creates the latency model of each link
+    pub latency: Box<dyn Fn() -> Box<dyn LatencyModel>>,
+    /// The bandwidth of each direction of each link (unlimited if None)
+    pub bandwidth: Option<Bandwidth>,
+    /// Creates the callback of each link
+    pub callback: Box<dyn Fn() -> Box<dyn LinkCallback<Message, Data>>>,
+}
+
+impl<Message: NetworkMessage, Data: NodeData> LinkSpec<Message, Data> {
+    /// Links with a fixed latency and unlimited bandwidth
+    pub fn new(
+        latency: Latency,
+        callback: impl Fn() -> Box<dyn LinkCallback<Message, Data>> + 'static,
+    ) -> Self {
+        Self {
+            latency: Box::new(move || Box::new(ConstantLatency(latency))),
+            bandwidth: None,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Connect two nodes, using the given latency and bandwidth instead of the spec's if set
+    fn connect(
+        &self,
+        node1: &Rc<Node<Message, Data>>,
+        node2: &Rc<Node<Message, Data>>,
+        latency: Option<Latency>,
+        bandwidth: Option<Bandwidth>,
+    ) -> Rc<Link<Message, Data>> {
+        let latency = match latency {
+            Some(latency) => Box::new(ConstantLatency(latency)),
+            None => (self.latency)(),
+        };
+
+        let link = Node::connect(
+            node1.clone(),
+            node2.clone(),
+            latency.mean(),
+            (self.callback)(),
+        );
+        link.set_latency_model(latency);
+
+        if let Some(bandwidth) = bandwidth.or(self.bandwidth) {
+            link.set_bandwidth(&node1.get_identifier(), Some(bandwidth));
+            link.set_bandwidth(&node2.get_identifier(), Some(bandwidth));
+        }
+
+        link
+    }
+}
+
+/// The nodes and links of a generated network
+pub struct Topology<Message: NetworkMessage, Data: NodeData> {
+    /// Ordered by the index they were created with
+    pub nodes: Vec<Rc<Node<Message, Data>>>,
+    pub links: Vec<Rc<Link<Message, Data>>>,
+}
+
+/// Create the nodes and connect them along the given edges
+fn build<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    edges: impl IntoIterator<Item = (usize, usize)>,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let nodes: Vec<_> = (0..num_nodes).map(create_node).collect();
+    let links = edges
+        .into_iter()
+        .map(|(first, second)| links.connect(&nodes[first], &nodes[second], None, None))
+        .collect();
+
+    Topology { nodes, links }
+}
+
+/// Normalize an edge, so that every edge is only added once
+fn edge(first: usize, second: usize) -> (usize, usize) {
+    assert_ne!(first, second, "Nodes cannot be connected to themselves");
+    (first.min(second), first.max(second))
+}
+
+/// Node 0 is connected to all other nodes
+pub fn star<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let edges = (1..num_nodes).map(|idx| (0, idx));
+    build(num_nodes, edges, create_node, links)
+}
+
+/// Every node is connected to the next one
+pub fn line<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let edges = (1..num_nodes).map(|idx| (idx - 1, idx));
+    build(num_nodes, edges, create_node, links)
+}
+
+/// Like [`line()`], but the last node is also connected to the first one
+pub fn ring<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let edges: BTreeSet<_> = (0..num_nodes)
+        .filter(|_| num_nodes > 1)
+        .map(|idx| edge(idx, (idx + 1) % num_nodes))
+        .collect();
+    build(num_nodes, edges, create_node, links)
+}
+
+/// Every node is connected to every other node
+pub fn full_mesh<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let edges =
+        (0..num_nodes).flat_map(|first| (first + 1..num_nodes).map(move |second| (first, second)));
+    build(num_nodes, edges, create_node, links)
+}
+
+fn grid_edges(width: usize, height: usize, wrap: bool) -> BTreeSet<(usize, usize)> {
+    let mut edges = BTreeSet::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+
+            if x + 1 < width {
+                edges.insert(edge(idx, idx + 1));
+            } else if wrap && width > 2 {
+                edges.insert(edge(idx, y * width));
+            }
+
+            if y + 1 < height {
+                edges.insert(edge(idx, idx + width));
+            } else if wrap && height > 2 {
+                edges.insert(edge(idx, x));
+            }
+        }
+    }
+
+    edges
+}
+
+/// Nodes are laid out row by row and connected to their horizontal and vertical neighbors
+///
+/// The node at column `x` and row `y` has index `y * width + x`.
+pub fn grid<Message: NetworkMessage, Data: NodeData>(
+    width: usize,
+    height: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let edges = grid_edges(width, height, false);
+    build(width * height, edges, create_node, links)
+}
+
+/// Like [`grid`], but the last row and column are also connected to the first ones
+pub fn torus<Message: NetworkMessage, Data: NodeData>(
+    width: usize,
+    height: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    let edges = grid_edges(width, height, true);
+    build(width * height, edges, create_node, links)
+}
+
+/// A k-ary fat-tree, as commonly used in data centers
+///
+/// `k` must be even. Nodes are created in this order:
+/// (k/2)² core switches, then k²/2 aggregation switches, then k²/2 edge switches
+/// (both grouped by pod), and finally k³/4 hosts (grouped by edge switch).
+pub fn fat_tree<Message: NetworkMessage, Data: NodeData>(
+    k: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    assert!(k >= 2 && k.is_multiple_of(2), "k must be even");
+
+    let half = k / 2;
+    let num_core = half * half;
+    let num_aggregation = k * half;
+    let num_edge = k * half;
+    let num_hosts = k * half * half;
+
+    let aggregation = |pod: usize, idx: usize| num_core + pod * half + idx;
+    let edge_switch = |pod: usize, idx: usize| num_core + num_aggregation + pod * half + idx;
+    let host =
+        |switch: usize, idx: usize| num_core + num_aggregation + num_edge + switch * half + idx;
+
+    let mut edges = vec![];
+
+    for pod in 0..k {
+        for agg in 0..half {
+            // Aggregation switch `agg` connects to core switches agg*half..(agg+1)*half
+            for core in 0..half {
+                edges.push((agg * half + core, aggregation(pod, agg)));
+            }
+
+            for edge in 0..half {
+                edges.push((aggregation(pod, agg), edge_switch(pod, edge)));
+            }
+        }
+
+        for edge in 0..half {
+            for idx in 0..half {
+                edges.push((edge_switch(pod, edge), host(pod * half + edge, idx)));
+            }
+        }
+    }
+
+    let num_nodes = num_core + num_aggregation + num_edge + num_hosts;
+    build(num_nodes, edges, create_node, links)
+}
+
+/// Every pair of nodes is connected with the given probability
+pub fn erdos_renyi<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    probability: f64,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    assert!((0.0..=1.0).contains(&probability), "Invalid probability");
+
+    let edges: Vec<_> = with_rng(|rng| {
+        (0..num_nodes)
+            .flat_map(|first| (first + 1..num_nodes).map(move |second| (first, second)))
+            .filter(|_| rng.random_bool(probability))
+            .collect()
+    });
+
+    build(num_nodes, edges, create_node, links)
+}
+
+/// A scale-free network grown by preferential attachment
+///
+/// Starts with `num_links + 1` fully connected nodes. Every further node connects
+/// to `num_links` existing nodes, chosen with probability proportional to their degree.
+pub fn barabasi_albert<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    num_links: usize,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    assert!(num_links >= 1, "Every node needs at least one link");
+    assert!(num_nodes > num_links, "Need more nodes than links per node");
+
+    let mut edges = vec![];
+    // Every node shows up once per link it has
+    let mut endpoints = vec![];
+
+    for first in 0..=num_links {
+        for second in first + 1..=num_links {
+            edges.push((first, second));
+            endpoints.extend([first, second]);
+        }
+    }
+
+    with_rng(|rng| {
+        for node in num_links + 1..num_nodes {
+            let mut targets = BTreeSet::new();
+
+            while targets.len() < num_links {
+                targets.insert(endpoints[rng.random_range(0..endpoints.len())]);
+            }
+
+            for target in targets {
+                edges.push((target, node));
+                endpoints.extend([target, node]);
+            }
+        }
+    });
+
+    build(num_nodes, edges, create_node, links)
+}
+
+/// A small-world network
+///
+/// Starts with a ring where every node is connected to its `num_neighbors` closest nodes
+/// (which must be even). Then every link is rewired to a random node with probability `beta`.
+pub fn watts_strogatz<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    num_neighbors: usize,
+    beta: f64,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> Topology<Message, Data> {
+    assert!(
+        num_neighbors.is_multiple_of(2),
+        "The number of neighbors must be even"
+    );
+    assert!(num_neighbors < num_nodes, "Need more nodes than neighbors");
+    assert!((0.0..=1.0).contains(&beta), "Invalid probability");
+
+    let mut edges = BTreeSet::new();
+    for node in 0..num_nodes {
+        for offset in 1..=num_neighbors / 2 {
+            edges.insert(edge(node, (node + offset) % num_nodes));
+        }
+    }
+
+    with_rng(|rng| {
+        for node in 0..num_nodes {
+            for offset in 1..=num_neighbors / 2 {
+                let current = edge(node, (node + offset) % num_nodes);
+                if !rng.random_bool(beta) || !edges.contains(&current) {
+                    continue;
+                }
+
+                // Give up if the node is already connected to everybody
+                let degree = edges
+                    .iter()
+                    .filter(|(a, b)| *a == node || *b == node)
+                    .count();
+                if degree >= num_nodes - 1 {
+                    continue;
+                }
+
+                let target = loop {
+                    let target = rng.random_range(0..num_nodes);
+                    if target != node && !edges.contains(&edge(node, target)) {
+                        break target;
+                    }
+                };
+
+                edges.remove(&current);
+                edges.insert(edge(node, target));
+            }
+        }
+    });
+
+    build(num_nodes, edges, create_node, links)
+}
+
+/// A geographic network
+///
+/// Nodes are placed randomly in the unit square. Two nodes at distance `d` are connected
+/// with probability `beta * exp(-d / (alpha * L))`, where `L` is the largest possible distance.
+/// Returns the position of each node as well.
+#[allow(clippy::type_complexity)]
+pub fn waxman<Message: NetworkMessage, Data: NodeData>(
+    num_nodes: usize,
+    alpha: f64,
+    beta: f64,
+    create_node: impl FnMut(usize) -> Rc<Node<Message, Data>>,
+    links: &LinkSpec<Message, Data>,
+) -> (Topology<Message, Data>, Vec<(f64, f64)>) {
+    assert!(alpha > 0.0, "alpha must be positive");
+    assert!((0.0..=1.0).contains(&beta), "Invalid probability");
+
+    let max_distance = std::f64::consts::SQRT_2;
+
+    let (positions, edges) = with_rng(|rng| {
+        let positions: Vec<(f64, f64)> = (0..num_nodes)
+            .map(|_| (rng.random(), rng.random()))
+            .collect();
+
+        let mut edges = vec![];
+        for first in 0..num_nodes {
+            for second in first + 1..num_nodes {
+                let (x1, y1) = positions[first];
+                let (x2, y2) = positions[second];
+                let distance = (x1 - x2).hypot(y1 - y2);
+
+                let probability = beta * (-distance / (alpha * max_distance)).exp();
+                if rng.random_bool(probability) {
+                    edges.push((first, second));
+                }
+            }
+        }
+
+        (positions, edges)
+    });
+
+    (build(num_nodes, edges, create_node, links), positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::network::{
+        DummyLinkCallback, DummyNetworkMessage, DummyNodeCallback, DummyNodeData, Object,
+    };
+    use crate::time::Duration;
+
+    type TestTopology = Topology<DummyNetworkMessage, DummyNodeData>;
+
+    fn create_node(_idx: usize) -> Rc<Node<DummyNetworkMessage, DummyNodeData>> {
+        Node::new(
+            Bandwidth::from_megabits_per_second(100),
+            DummyNodeData::default(),
+            Box::new(DummyNodeCallback::default()),
+        )
+    }
+
+    fn link_spec() -> LinkSpec<DummyNetworkMessage, DummyNodeData> {
+        LinkSpec::new(Duration::from_millis(5), || {
+            Box::new(DummyLinkCallback::default())
+        })
+    }
+
+    /// The links as pairs of node indices
+    fn edges(topology: &TestTopology) -> Vec<(usize, usize)> {
+        let index_of = |node: &Rc<Node<DummyNetworkMessage, DummyNodeData>>| {
+            topology
+                .nodes
+                .iter()
+                .position(|other| other.get_identifier() == node.get_identifier())
+                .unwrap()
+        };
+
+        let mut edges: Vec<_> = topology
+            .links
+            .iter()
+            .map(|link| {
+                let (node1, node2) = link.get_nodes();
+                edge(index_of(node1), index_of(node2))
+            })
+            .collect();
+
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn deterministic_shapes() {
+        crate::Runtime::new().block_on(async {
+            let links = link_spec();
+
+            let topology = star(5, create_node, &links);
+            assert_eq!(edges(&topology), vec![(0, 1), (0, 2), (0, 3), (0, 4)]);
+
+            let topology = line(3, create_node, &links);
+            assert_eq!(edges(&topology), vec![(0, 1), (1, 2)]);
+
+            let topology = ring(4, create_node, &links);
+            assert_eq!(edges(&topology), vec![(0, 1), (0, 3), (1, 2), (2, 3)]);
+
+            assert_eq!(full_mesh(6, create_node, &links).links.len(), 15);
+            assert_eq!(grid(4, 3, create_node, &links).links.len(), 17);
+            assert_eq!(torus(4, 3, create_node, &links).links.len(), 24);
+
+            let topology = fat_tree(4, create_node, &links);
+            assert_eq!(topology.nodes.len(), 36);
+            assert_eq!(topology.links.len(), 48);
+            assert!(topology.nodes[35].num_peers() == 1);
+            assert!(topology.nodes[0].num_peers() == 4);
+        });
+    }
+
+    fn random_shapes(seed: u64) -> Vec<Vec<(usize, usize)>> {
+        let runtime = crate::Runtime::new();
+        runtime.set_seed(seed);
+
+        let _ctx = runtime.with_context();
+        let links = link_spec();
+
+        let topologies = [
+            erdos_renyi(20, 0.2, create_node, &links),
+            barabasi_albert(20, 2, create_node, &links),
+            watts_strogatz(20, 4, 0.3, create_node, &links),
+            waxman(20, 0.4, 0.8, create_node, &links).0,
+        ];
+
+        assert_eq!(topologies[1].links.len(), 3 + 17 * 2);
+        assert_eq!(topologies[2].links.len(), 40);
+
+        topologies.iter().map(edges).collect()
+    }
+
+    #[test]
+    fn random_shapes_are_reproducible() {
+        assert_eq!(random_shapes(7), random_shapes(7));
+        assert_ne!(random_shapes(7), random_shapes(8));
+    }
+}
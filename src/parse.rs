@@ -0,0 +1,161 @@
+/// Helpers to parse and serialize quantities with human-readable units
+use std::fmt::Display;
+
+/// Error returned when parsing a value with units failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Split a string like "2.5s" into its number ("2.5") and unit ("s")
+pub(crate) fn split_quantity(input: &str) -> Result<(&str, &str), ParseError> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    if number.is_empty() {
+        return Err(ParseError::new(format!("Missing number in \"{input}\"")));
+    }
+
+    Ok((number, unit.trim()))
+}
+
+/// Parse a decimal number and multiply it by `scale`
+///
+/// This does not use floating point arithmetic so that the result is exact.
+/// Fails if the result is not a whole number or does not fit into 64 bits.
+pub(crate) fn parse_scaled(number: &str, scale: u64) -> Result<u64, ParseError> {
+    let invalid = || ParseError::new(format!("Invalid number \"{number}\""));
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+
+    let mut result = whole * (scale as u128);
+
+    if !fraction.is_empty() {
+        let digits = u32::try_from(fraction.len()).map_err(|_| invalid())?;
+        let divisor = 10u128.checked_pow(digits).ok_or_else(invalid)?;
+        let fraction: u128 = fraction.parse().map_err(|_| invalid())?;
+        let scaled = fraction * (scale as u128);
+
+        if !scaled.is_multiple_of(divisor) {
+            return Err(ParseError::new(format!(
+                "\"{number}\" is more precise than supported"
+            )));
+        }
+
+        result += scaled / divisor;
+    }
+
+    result
+        .try_into()
+        .map_err(|_| ParseError::new(format!("\"{number}\" is too large")))
+}
+
+/// A quantity that is stored as an integer but can be written with units
+#[cfg(feature = "serde")]
+pub(crate) trait Quantity: std::str::FromStr<Err = ParseError> + Display + Sized {
+    const EXPECTING: &'static str;
+
+    fn from_raw(value: u64) -> Self;
+    fn to_raw(&self) -> u64;
+}
+
+/// Serializes as a string with units for human-readable formats
+/// and as an integer otherwise
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_quantity<Q: Quantity, S: serde::Serializer>(
+    quantity: &Q,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.collect_str(quantity)
+    } else {
+        serializer.serialize_u64(quantity.to_raw())
+    }
+}
+
+/// Accepts both strings with units and plain integers
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_quantity<'de, Q: Quantity, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Q, D::Error> {
+    struct Visitor<Q>(std::marker::PhantomData<Q>);
+
+    impl<Q: Quantity> serde::de::Visitor<'_> for Visitor<Q> {
+        type Value = Q;
+
+        fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fmt.write_str(Q::EXPECTING)
+        }
+
+        fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Q, E> {
+            Ok(Q::from_raw(value))
+        }
+
+        fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Q, E> {
+            let value = u64::try_from(value).map_err(E::custom)?;
+            Ok(Q::from_raw(value))
+        }
+
+        fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Q, E> {
+            value.parse().map_err(E::custom)
+        }
+    }
+
+    let visitor = Visitor(std::marker::PhantomData);
+
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(visitor)
+    } else {
+        deserializer.deserialize_u64(visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_scaled, split_quantity};
+
+    #[test]
+    fn split() {
+        assert_eq!(split_quantity("2.5s"), Ok(("2.5", "s")));
+        assert_eq!(split_quantity(" 100 Mbit/s "), Ok(("100", "Mbit/s")));
+        assert!(split_quantity("ms").is_err());
+    }
+
+    #[test]
+    fn scaled() {
+        assert_eq!(parse_scaled("2.5", 1000), Ok(2500));
+        assert_eq!(parse_scaled(".25", 100), Ok(25));
+        assert_eq!(parse_scaled("7", 3), Ok(21));
+        assert!(parse_scaled("0.0001", 1000).is_err());
+        assert!(parse_scaled(".", 1).is_err());
+        assert!(parse_scaled("1.2.3", 1).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+/// Randomness that can be reproduced across runs
+///
+/// Every runtime owns a random number generator. It is seeded randomly,
+/// unless a seed is set with [`crate::Runtime::set_seed`].
+/// Simulations (and asim itself, e.g., for link faults) should draw all their randomness
+/// from it, so that a run can be repeated exactly by reusing its seed.
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+pub use rand::{Rng, RngCore};
+
+pub(crate) struct SeededRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for SeededRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+/// Run a function with the random number generator of the current context
+///
+/// Note, this will panic if no asim context is active,
+/// or if `func` calls `with_rng` again
+pub fn with_rng<R>(func: impl FnOnce(&mut StdRng) -> R) -> R {
+    crate::CONTEXT.with(|hdl| {
+        let hdl = hdl.borrow();
+        let hdl = hdl.as_ref().expect("Not in an asim context!");
+        let mut rng = hdl.get_rng().borrow_mut();
+        func(&mut rng.rng)
+    })
+}
+
+/// The seed of the current context's random number generator
+///
+/// Note, this will panic if no asim context is active
+pub fn seed() -> u64 {
+    crate::get_runtime().get_seed()
+}
+
+/// Returns true with the given probability
+///
+/// Does not draw from the random number generator if the outcome is certain
+#[cfg(feature = "network")]
+pub(crate) fn chance(probability: f64) -> bool {
+    if probability <= 0.0 {
+        false
+    } else if probability >= 1.0 {
+        true
+    } else {
+        with_rng(|rng| rng.random_bool(probability))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    fn draw(seed: u64) -> Vec<u32> {
+        let runtime = crate::Runtime::new();
+        runtime.set_seed(seed);
+
+        let _ctx = runtime.with_context();
+        assert_eq!(super::seed(), seed);
+        (0..10)
+            .map(|_| super::with_rng(|rng| rng.random()))
+            .collect()
+    }
+
+    #[test]
+    fn reproducible() {
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+    }
+}
@@ -0,0 +1,239 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::stats::{ContainerStats, TimeAverage};
+use crate::deadlock::Resource;
+use crate::sync::wait_queue::WaitQueue;
+
+struct Inner {
+    level: f64,
+    capacity: f64,
+    putters: WaitQueue,
+    getters: WaitQueue,
+    stats: ContainerStats,
+}
+
+impl Inner {
+    /// Let the next task on either side re-check whether it can proceed
+    fn wake_both(&mut self) {
+        self.putters.wake_next();
+        self.getters.wake_next();
+    }
+
+    fn update_stats(&mut self) {
+        self.stats.level.set(self.level);
+        self.stats.get_queue_length.set(self.getters.len() as f64);
+        self.stats.put_queue_length.set(self.putters.len() as f64);
+    }
+}
+
+/// Holds a continuous amount of something, e.g., fuel in a tank or energy in a battery
+///
+/// Both putters and getters are served in FIFO order,
+/// so a large request will block smaller ones behind it.
+pub struct Container {
+    inner: RefCell<Inner>,
+}
+
+impl Container {
+    /// Create a container that holds at most `capacity` and is filled with `init`
+    #[track_caller]
+    pub fn new(capacity: f64, init: f64) -> Self {
+        assert!(capacity > 0.0, "Container capacity must be positive");
+        assert!(
+            (0.0..=capacity).contains(&init),
+            "Initial level must be between zero and the capacity"
+        );
+
+        Self {
+            inner: RefCell::new(Inner {
+                level: init,
+                capacity,
+                putters: WaitQueue::new(Resource::new("Container")),
+                getters: WaitQueue::new(Resource::new("Container")),
+                stats: ContainerStats {
+                    capacity,
+                    puts: 0,
+                    gets: 0,
+                    level: TimeAverage::new(init),
+                    get_queue_length: TimeAverage::new(0.0),
+                    put_queue_length: TimeAverage::new(0.0),
+                },
+            }),
+        }
+    }
+
+    /// Add `amount`, waiting until there is enough space
+    pub fn put(&self, amount: f64) -> ContainerPutFut<'_> {
+        assert!(
+            (0.0..=self.capacity()).contains(&amount),
+            "Amount must be between zero and the capacity"
+        );
+
+        ContainerPutFut {
+            container: self,
+            amount,
+            identifier: None,
+        }
+    }
+
+    /// Take `amount`, waiting until enough is available
+    pub fn get(&self, amount: f64) -> ContainerGetFut<'_> {
+        assert!(
+            (0.0..=self.capacity()).contains(&amount),
+            "Amount must be between zero and the capacity"
+        );
+
+        ContainerGetFut {
+            container: self,
+            amount,
+            identifier: None,
+        }
+    }
+
+    /// The amount currently stored
+    pub fn level(&self) -> f64 {
+        self.inner.borrow().level
+    }
+
+    pub fn capacity(&self) -> f64 {
+        self.inner.borrow().capacity
+    }
+
+    pub fn stats(&self) -> ContainerStats {
+        self.inner.borrow().stats.clone()
+    }
+}
+
+pub struct ContainerPutFut<'a> {
+    container: &'a Container,
+    amount: f64,
+    /// Set once we are in the waiter queue
+    identifier: Option<u64>,
+}
+
+impl Future for ContainerPutFut<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        let inner = &mut *this.container.inner.borrow_mut();
+
+        if inner.putters.is_next(this.identifier) && inner.level + this.amount <= inner.capacity {
+            if let Some(identifier) = this.identifier.take() {
+                inner.putters.remove(identifier);
+            }
+
+            inner.level += this.amount;
+            inner.stats.puts += 1;
+
+            inner.wake_both();
+            inner.update_stats();
+            Poll::Ready(())
+        } else {
+            inner.putters.register(&mut this.identifier, ctx.waker());
+            inner.update_stats();
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for ContainerPutFut<'_> {
+    fn drop(&mut self) {
+        if let Some(identifier) = self.identifier.take() {
+            let mut inner = self.container.inner.borrow_mut();
+
+            // Pass on our turn, so the wakeup is not lost
+            if inner.putters.remove(identifier) {
+                inner.putters.wake_next();
+            }
+
+            inner.update_stats();
+        }
+    }
+}
+
+pub struct ContainerGetFut<'a> {
+    container: &'a Container,
+    amount: f64,
+    /// Set once we are in the waiter queue
+    identifier: Option<u64>,
+}
+
+impl Future for ContainerGetFut<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        let inner = &mut *this.container.inner.borrow_mut();
+
+        if inner.getters.is_next(this.identifier) && inner.level >= this.amount {
+            if let Some(identifier) = this.identifier.take() {
+                inner.getters.remove(identifier);
+            }
+
+            inner.level -= this.amount;
+            inner.stats.gets += 1;
+
+            inner.wake_both();
+            inner.update_stats();
+            Poll::Ready(())
+        } else {
+            inner.getters.register(&mut this.identifier, ctx.waker());
+            inner.update_stats();
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for ContainerGetFut<'_> {
+    fn drop(&mut self) {
+        if let Some(identifier) = self.identifier.take() {
+            let mut inner = self.container.inner.borrow_mut();
+
+            // Pass on our turn, so the wakeup is not lost
+            if inner.getters.remove(identifier) {
+                inner.getters.wake_next();
+            }
+
+            inner.update_stats();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Container;
+    use crate::time::Duration;
+
+    #[test]
+    fn refuel() {
+        crate::Runtime::new().block_on(async {
+            let tank = Rc::new(Container::new(100.0, 20.0));
+
+            {
+                let tank = tank.clone();
+                crate::spawn(async move {
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                    tank.put(30.0).await;
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                    tank.put(30.0).await;
+                });
+            }
+
+            tank.get(60.0).await;
+            assert_eq!(crate::time::now().to_millis(), 20);
+            assert_eq!(tank.level(), 20.0);
+
+            let stats = tank.stats();
+            assert_eq!((stats.puts, stats.gets), (2, 1));
+            assert_eq!(stats.level.max(), 80.0);
+            // 20 for 10ms and 50 for 10ms
+            assert_eq!(stats.level.mean(), 35.0);
+        });
+    }
+}
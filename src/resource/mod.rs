@@ -0,0 +1,18 @@
+/// Shared resources in the style of SimPy
+///
+/// These model contention in a simulation (servers, buffers, tanks)
+/// rather than synchronization between tasks, and they keep track of
+/// queue lengths and utilization over simulated time.
+mod resources;
+pub use resources::{
+    PreemptedFut, PreemptiveResource, PriorityResource, RequestFut, Resource, ResourceGuard,
+};
+
+mod store;
+pub use store::{GetFut, PutFut, Store};
+
+mod container;
+pub use container::{Container, ContainerGetFut, ContainerPutFut};
+
+mod stats;
+pub use stats::{ContainerStats, ResourceStats, StoreStats, TimeAverage};
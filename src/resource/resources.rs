@@ -0,0 +1,531 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use super::stats::{ResourceStats, TimeAverage};
+use crate::time::Time;
+
+/// Position in the request queue; higher priorities come first, then FIFO
+type QueueKey = (Reverse<i64>, u64);
+
+struct User {
+    identifier: u64,
+    priority: i64,
+    /// Set if the holder waits for being preempted
+    preempt_waker: Option<Waker>,
+}
+
+struct Inner {
+    capacity: usize,
+    preemptive: bool,
+    /// Users in the order they were granted the resource
+    users: Vec<User>,
+    /// Users that lost the resource but have not dropped their guard yet
+    preempted: HashSet<u64>,
+    queue: BTreeMap<QueueKey, Waker>,
+    wait_start: HashMap<u64, Time>,
+    next_identifier: u64,
+    stats: ResourceStats,
+}
+
+impl Inner {
+    fn has_space(&self) -> bool {
+        self.users.len() < self.capacity
+    }
+
+    fn wake_next(&self) {
+        if self.has_space() {
+            if let Some(waker) = self.queue.values().next() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+
+    fn update_stats(&mut self) {
+        self.stats.users.set(self.users.len() as f64);
+        self.stats.queue_length.set(self.queue.len() as f64);
+    }
+
+    /// Find the user that should be preempted by a request with the given priority
+    ///
+    /// This is the user with the lowest priority that started using the resource last
+    fn find_victim(&self, priority: i64) -> Option<usize> {
+        if !self.preemptive {
+            return None;
+        }
+
+        self.users
+            .iter()
+            .enumerate()
+            .rev()
+            .min_by_key(|(_, user)| user.priority)
+            .filter(|(_, user)| user.priority < priority)
+            .map(|(pos, _)| pos)
+    }
+
+    fn preempt(&mut self, pos: usize) {
+        let user = self.users.remove(pos);
+        log::trace!("Request #{} got preempted", user.identifier);
+
+        self.preempted.insert(user.identifier);
+        self.stats.preemptions += 1;
+
+        if let Some(waker) = user.preempt_waker {
+            waker.wake();
+        }
+    }
+
+    fn release(&mut self, identifier: u64) {
+        if let Some(pos) = self.users.iter().position(|u| u.identifier == identifier) {
+            self.users.remove(pos);
+            self.update_stats();
+            self.wake_next();
+        } else {
+            self.preempted.remove(&identifier);
+        }
+    }
+}
+
+/// Shared implementation of all resource variants
+struct ResourceCore {
+    inner: RefCell<Inner>,
+}
+
+impl ResourceCore {
+    fn new(capacity: usize, preemptive: bool) -> Self {
+        assert!(capacity > 0, "Resource capacity must be at least one");
+
+        Self {
+            inner: RefCell::new(Inner {
+                capacity,
+                preemptive,
+                users: vec![],
+                preempted: HashSet::new(),
+                queue: BTreeMap::new(),
+                wait_start: HashMap::new(),
+                next_identifier: 0,
+                stats: ResourceStats {
+                    capacity,
+                    requests: 0,
+                    preemptions: 0,
+                    wait_times: Default::default(),
+                    queue_length: TimeAverage::new(0.0),
+                    users: TimeAverage::new(0.0),
+                },
+            }),
+        }
+    }
+
+    fn request(&self, priority: i64) -> RequestFut<'_> {
+        RequestFut {
+            core: self,
+            priority,
+            key: None,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.inner.borrow().users.len()
+    }
+
+    fn queue_len(&self) -> usize {
+        self.inner.borrow().queue.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.inner.borrow().capacity
+    }
+
+    fn stats(&self) -> ResourceStats {
+        self.inner.borrow().stats.clone()
+    }
+}
+
+/// A resource that can be used by a limited number of tasks at a time, e.g., a server with `capacity` CPUs
+///
+/// Requests are served in FIFO order.
+pub struct Resource {
+    core: ResourceCore,
+}
+
+/// Like [`Resource`] but requests with higher priority are served first
+///
+/// Requests with the same priority are served in FIFO order.
+pub struct PriorityResource {
+    core: ResourceCore,
+}
+
+/// Like [`PriorityResource`] but a request can take the resource away from
+/// a user with lower priority
+///
+/// Preempted users should stop what they are doing;
+/// use [`ResourceGuard::preempted`] to find out when this happens.
+pub struct PreemptiveResource {
+    core: ResourceCore,
+}
+
+impl Resource {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            core: ResourceCore::new(capacity, false),
+        }
+    }
+
+    /// Wait until the resource can be used
+    pub fn request(&self) -> RequestFut<'_> {
+        self.core.request(0)
+    }
+
+    /// The number of tasks currently using the resource
+    pub fn count(&self) -> usize {
+        self.core.count()
+    }
+
+    /// The number of requests waiting for the resource
+    pub fn queue_len(&self) -> usize {
+        self.core.queue_len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.core.capacity()
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        self.core.stats()
+    }
+}
+
+impl PriorityResource {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            core: ResourceCore::new(capacity, false),
+        }
+    }
+
+    /// Wait until the resource can be used
+    ///
+    /// Like task priorities, higher values are served first
+    pub fn request(&self, priority: i64) -> RequestFut<'_> {
+        self.core.request(priority)
+    }
+
+    /// The number of tasks currently using the resource
+    pub fn count(&self) -> usize {
+        self.core.count()
+    }
+
+    /// The number of requests waiting for the resource
+    pub fn queue_len(&self) -> usize {
+        self.core.queue_len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.core.capacity()
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        self.core.stats()
+    }
+}
+
+impl PreemptiveResource {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            core: ResourceCore::new(capacity, true),
+        }
+    }
+
+    /// Wait until the resource can be used
+    ///
+    /// If the resource is fully used, this preempts the user with the lowest priority
+    /// (that started using the resource last), if its priority is lower than the
+    /// priority of this request.
+    pub fn request(&self, priority: i64) -> RequestFut<'_> {
+        self.core.request(priority)
+    }
+
+    /// The number of tasks currently using the resource
+    pub fn count(&self) -> usize {
+        self.core.count()
+    }
+
+    /// The number of requests waiting for the resource
+    pub fn queue_len(&self) -> usize {
+        self.core.queue_len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.core.capacity()
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        self.core.stats()
+    }
+}
+
+pub struct RequestFut<'a> {
+    core: &'a ResourceCore,
+    priority: i64,
+    /// Set once we are in the queue
+    key: Option<QueueKey>,
+}
+
+/// Grants use of a resource until it is dropped
+#[must_use]
+pub struct ResourceGuard<'a> {
+    core: &'a ResourceCore,
+    identifier: u64,
+}
+
+impl<'a> Future for RequestFut<'a> {
+    type Output = ResourceGuard<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<ResourceGuard<'a>> {
+        let this = &mut *self;
+        let inner = &mut *this.core.inner.borrow_mut();
+
+        // Only the request at the front of the queue may proceed,
+        // or a new one that has higher priority than all queued requests
+        let is_next = match (inner.queue.keys().next(), this.key) {
+            (None, _) => true,
+            (Some(first), Some(key)) => *first == key,
+            (Some((Reverse(first), _)), None) => this.priority > *first,
+        };
+
+        if is_next && !inner.has_space() {
+            if let Some(pos) = inner.find_victim(this.priority) {
+                inner.preempt(pos);
+            }
+        }
+
+        if !is_next || !inner.has_space() {
+            if let Some(key) = this.key {
+                inner.queue.insert(key, ctx.waker().clone());
+            } else {
+                let identifier = inner.next_identifier;
+                inner.next_identifier += 1;
+
+                let key = (Reverse(this.priority), identifier);
+                inner.queue.insert(key, ctx.waker().clone());
+                inner.wait_start.insert(identifier, crate::time::now());
+                inner.update_stats();
+                this.key = Some(key);
+            }
+
+            return Poll::Pending;
+        }
+
+        let identifier = if let Some(key) = this.key.take() {
+            inner.queue.remove(&key);
+
+            let (_, identifier) = key;
+            let start = inner.wait_start.remove(&identifier).unwrap();
+            let waited = crate::time::now().saturating_duration_since(start);
+            inner.stats.wait_times.record(waited);
+            identifier
+        } else {
+            let identifier = inner.next_identifier;
+            inner.next_identifier += 1;
+            identifier
+        };
+
+        inner.users.push(User {
+            identifier,
+            priority: this.priority,
+            preempt_waker: None,
+        });
+        inner.stats.requests += 1;
+        inner.update_stats();
+
+        // There might be space for more
+        inner.wake_next();
+
+        Poll::Ready(ResourceGuard {
+            core: this.core,
+            identifier,
+        })
+    }
+}
+
+impl Drop for RequestFut<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let inner = &mut *self.core.inner.borrow_mut();
+            inner.queue.remove(&key);
+            inner.wait_start.remove(&key.1);
+            inner.update_stats();
+
+            // Pass on the wakeup we might have gotten
+            inner.wake_next();
+        }
+    }
+}
+
+impl ResourceGuard<'_> {
+    /// Has a request with higher priority taken the resource away?
+    ///
+    /// This can only happen for [`PreemptiveResource`]
+    pub fn is_preempted(&self) -> bool {
+        self.core
+            .inner
+            .borrow()
+            .preempted
+            .contains(&self.identifier)
+    }
+
+    /// Wait until a request with higher priority takes the resource away
+    ///
+    /// Never completes unless the resource is a [`PreemptiveResource`]
+    pub fn preempted(&self) -> PreemptedFut<'_> {
+        PreemptedFut {
+            core: self.core,
+            identifier: self.identifier,
+        }
+    }
+}
+
+impl Drop for ResourceGuard<'_> {
+    fn drop(&mut self) {
+        self.core.inner.borrow_mut().release(self.identifier);
+    }
+}
+
+pub struct PreemptedFut<'a> {
+    core: &'a ResourceCore,
+    identifier: u64,
+}
+
+impl Future for PreemptedFut<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        let mut inner = self.core.inner.borrow_mut();
+
+        if inner.preempted.contains(&self.identifier) {
+            return Poll::Ready(());
+        }
+
+        let user = inner
+            .users
+            .iter_mut()
+            .find(|user| user.identifier == self.identifier)
+            .expect("Resource user vanished");
+        user.preempt_waker = Some(ctx.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::pin::pin;
+    use std::rc::Rc;
+
+    use futures::future::{self, Either};
+
+    use super::{PreemptiveResource, PriorityResource, Resource};
+    use crate::time::Duration;
+
+    #[test]
+    fn fifo_and_stats() {
+        crate::Runtime::new().block_on(async {
+            let resource = Rc::new(Resource::new(2));
+            let done = Rc::new(RefCell::new(vec![]));
+
+            for idx in 0..4 {
+                let resource = resource.clone();
+                let done = done.clone();
+
+                crate::spawn(async move {
+                    let _guard = resource.request().await;
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                    done.borrow_mut()
+                        .push((idx, crate::time::now().to_millis()));
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(1)).await;
+            assert_eq!(resource.count(), 2);
+            assert_eq!(resource.queue_len(), 2);
+
+            crate::time::sleep(Duration::from_millis(39)).await;
+            assert_eq!(*done.borrow(), vec![(0, 10), (1, 10), (2, 20), (3, 20)]);
+
+            // Busy for 20 of 40ms, two requests waited for 10ms each
+            let stats = resource.stats();
+            assert_eq!(stats.requests, 4);
+            assert_eq!(stats.wait_times.total(), Duration::from_millis(20));
+            assert_eq!(stats.utilization(), 0.5);
+            assert_eq!(stats.queue_length.max(), 2.0);
+            assert_eq!(stats.queue_length.mean(), 0.5);
+        });
+    }
+
+    #[test]
+    fn priority() {
+        crate::Runtime::new().block_on(async {
+            let resource = Rc::new(PriorityResource::new(1));
+            let order = Rc::new(RefCell::new(vec![]));
+            let guard = resource.request(0).await;
+
+            for priority in [1, 3, 2, 3] {
+                let resource = resource.clone();
+                let order = order.clone();
+
+                crate::spawn(async move {
+                    let _guard = resource.request(priority).await;
+                    order.borrow_mut().push(priority);
+                    crate::time::sleep(Duration::from_millis(1)).await;
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(1)).await;
+            drop(guard);
+
+            crate::time::sleep(Duration::from_millis(10)).await;
+            assert_eq!(*order.borrow(), vec![3, 3, 2, 1]);
+        });
+    }
+
+    #[test]
+    fn preemption() {
+        crate::Runtime::new().block_on(async {
+            let resource = Rc::new(PreemptiveResource::new(1));
+
+            {
+                let resource = resource.clone();
+                crate::spawn(async move {
+                    crate::time::sleep(Duration::from_millis(5)).await;
+                    let _guard = resource.request(1).await;
+                    assert_eq!(crate::time::now().to_millis(), 5);
+                    crate::time::sleep(Duration::from_millis(5)).await;
+                });
+            }
+
+            let guard = resource.request(0).await;
+
+            {
+                let work = pin!(crate::time::sleep(Duration::from_millis(20)));
+                let preempted = pin!(guard.preempted());
+                let result = future::select(work, preempted).await;
+                assert!(
+                    matches!(result, Either::Right(_)),
+                    "Should have been preempted"
+                );
+            }
+
+            assert!(guard.is_preempted());
+            assert_eq!(crate::time::now().to_millis(), 5);
+            drop(guard);
+
+            // A request with equal priority does not preempt, but waits
+            let _guard = resource.request(0).await;
+            assert_eq!(crate::time::now().to_millis(), 10);
+            assert_eq!(resource.stats().preemptions, 1);
+        });
+    }
+}
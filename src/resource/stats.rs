@@ -0,0 +1,157 @@
+use crate::sync::Histogram;
+use crate::time::{Duration, Time};
+
+/// The current time, or None if called outside of an asim context
+fn try_now() -> Option<Time> {
+    crate::CONTEXT.with(|hdl| hdl.borrow().as_ref().map(|hdl| hdl.get_timer().now()))
+}
+
+/// Tracks a value that changes over simulated time, e.g., a queue length,
+/// and its time-weighted average
+#[derive(Debug, Clone)]
+pub struct TimeAverage {
+    /// When tracking started; None if the value was created outside of an asim context
+    /// and has not changed since
+    start: Option<Time>,
+    last_change: Option<Time>,
+    value: f64,
+    max: f64,
+    /// Sum of value times nanoseconds since start until the last change
+    integral: f64,
+}
+
+impl TimeAverage {
+    pub(super) fn new(value: f64) -> Self {
+        let now = try_now();
+
+        Self {
+            start: now,
+            last_change: now,
+            value,
+            max: value,
+            integral: 0.0,
+        }
+    }
+
+    pub(super) fn set(&mut self, value: f64) {
+        self.max = self.max.max(value);
+
+        let Some(now) = try_now() else {
+            self.value = value;
+            return;
+        };
+
+        if let Some(last_change) = self.last_change {
+            let elapsed = now.saturating_duration_since(last_change);
+            self.integral += self.value * elapsed.as_nanos() as f64;
+        } else {
+            self.start = Some(now);
+        }
+
+        self.last_change = Some(now);
+        self.value = value;
+    }
+
+    /// The value right now
+    pub fn current(&self) -> f64 {
+        self.value
+    }
+
+    /// The largest value seen so far
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// How long the value has been tracked
+    pub fn elapsed(&self) -> Duration {
+        match (self.start, try_now()) {
+            (Some(start), Some(now)) => now.saturating_duration_since(start),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The average value, weighted by how long each value was held
+    pub fn mean(&self) -> f64 {
+        let (Some(start), Some(last_change), Some(now)) = (self.start, self.last_change, try_now())
+        else {
+            return self.value;
+        };
+
+        let total = now.saturating_duration_since(start).as_nanos();
+        if total == 0 {
+            return self.value;
+        }
+
+        let pending = now.saturating_duration_since(last_change).as_nanos() as f64;
+        (self.integral + self.value * pending) / total as f64
+    }
+}
+
+/// Statistics of a [`super::Resource`] (or one of its variants)
+#[derive(Debug, Clone)]
+pub struct ResourceStats {
+    pub capacity: usize,
+    /// How many requests were granted
+    pub requests: u64,
+    /// How many users were preempted by a higher-priority request
+    pub preemptions: u64,
+    /// How long granted requests waited in the queue
+    pub wait_times: Histogram,
+    /// The number of requests waiting
+    pub queue_length: TimeAverage,
+    /// The number of requests being served
+    pub users: TimeAverage,
+}
+
+impl ResourceStats {
+    /// The average fraction of the capacity that was in use
+    pub fn utilization(&self) -> f64 {
+        self.users.mean() / self.capacity as f64
+    }
+}
+
+/// Statistics of a [`super::Store`]
+#[derive(Debug, Clone)]
+pub struct StoreStats {
+    /// None if the store is unbounded
+    pub capacity: Option<usize>,
+    pub puts: u64,
+    pub gets: u64,
+    /// The number of items in the store
+    pub level: TimeAverage,
+    /// The number of tasks waiting to get an item
+    pub get_queue_length: TimeAverage,
+    /// The number of tasks waiting for space to put an item
+    pub put_queue_length: TimeAverage,
+}
+
+impl StoreStats {
+    /// The average fraction of the capacity that was in use (zero if unbounded)
+    pub fn utilization(&self) -> f64 {
+        match self.capacity {
+            Some(capacity) => self.level.mean() / capacity as f64,
+            None => 0.0,
+        }
+    }
+}
+
+/// Statistics of a [`super::Container`]
+#[derive(Debug, Clone)]
+pub struct ContainerStats {
+    pub capacity: f64,
+    pub puts: u64,
+    pub gets: u64,
+    /// The amount stored in the container
+    pub level: TimeAverage,
+    /// The number of tasks waiting to get from the container
+    pub get_queue_length: TimeAverage,
+    /// The number of tasks waiting for space to put into the container
+    pub put_queue_length: TimeAverage,
+}
+
+impl ContainerStats {
+    /// The average fraction of the capacity that was filled
+    pub fn utilization(&self) -> f64 {
+        self.level.mean() / self.capacity
+    }
+}
@@ -0,0 +1,357 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use super::stats::{StoreStats, TimeAverage};
+use crate::deadlock::Resource;
+use crate::sync::wait_queue::WaitQueue;
+
+type Filter<T> = Box<dyn Fn(&T) -> bool>;
+
+struct Getter<T> {
+    identifier: u64,
+    filter: Option<Filter<T>>,
+    waker: Waker,
+    /// Set once an item has been handed to this getter
+    item: Option<T>,
+}
+
+impl<T> Getter<T> {
+    fn wants(&self, item: &T) -> bool {
+        self.item.is_none() && self.filter.as_ref().is_none_or(|filter| filter(item))
+    }
+}
+
+struct Inner<T> {
+    items: VecDeque<T>,
+    capacity: Option<usize>,
+    /// Tasks waiting for an item, in the order they started waiting
+    getters: Vec<Getter<T>>,
+    next_getter_id: u64,
+    /// Tasks waiting for space
+    putters: WaitQueue,
+    stats: StoreStats,
+}
+
+impl<T> Inner<T> {
+    fn has_space(&self) -> bool {
+        self.capacity
+            .is_none_or(|capacity| self.items.len() < capacity)
+    }
+
+    /// Hand items to waiting getters
+    ///
+    /// Each item goes to the first getter whose filter accepts it
+    fn dispatch(&mut self) {
+        let mut pos = 0;
+
+        while pos < self.items.len() {
+            let item = &self.items[pos];
+
+            if let Some(getter) = self.getters.iter_mut().find(|g| g.wants(item)) {
+                getter.item = self.items.remove(pos);
+                getter.waker.wake_by_ref();
+            } else {
+                pos += 1;
+            }
+        }
+
+        if self.has_space() {
+            self.putters.wake_next();
+        }
+    }
+
+    fn update_stats(&mut self) {
+        self.stats.level.set(self.items.len() as f64);
+
+        let num_getters = self.getters.iter().filter(|g| g.item.is_none()).count();
+        self.stats.get_queue_length.set(num_getters as f64);
+        self.stats.put_queue_length.set(self.putters.len() as f64);
+    }
+}
+
+/// Holds items that tasks can put into and get out of, e.g., packets in a buffer
+///
+/// Getters are served in FIFO order, but a getter with a filter does not block
+/// getters behind it that want a different item.
+/// Putters are served in FIFO order as well.
+pub struct Store<T> {
+    inner: RefCell<Inner<T>>,
+}
+
+impl<T> Store<T> {
+    /// Create a store that holds at most `capacity` items
+    #[track_caller]
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Store capacity must be at least one");
+        Self::create(Some(capacity))
+    }
+
+    /// Create a store without a limit on the number of items
+    #[track_caller]
+    pub fn unbounded() -> Self {
+        Self::create(None)
+    }
+
+    #[track_caller]
+    fn create(capacity: Option<usize>) -> Self {
+        Self {
+            inner: RefCell::new(Inner {
+                items: VecDeque::new(),
+                capacity,
+                getters: vec![],
+                next_getter_id: 0,
+                putters: WaitQueue::new(Resource::new("Store")),
+                stats: StoreStats {
+                    capacity,
+                    puts: 0,
+                    gets: 0,
+                    level: TimeAverage::new(0.0),
+                    get_queue_length: TimeAverage::new(0.0),
+                    put_queue_length: TimeAverage::new(0.0),
+                },
+            }),
+        }
+    }
+
+    /// Put an item into the store, waiting until there is space
+    pub fn put(&self, item: T) -> PutFut<'_, T> {
+        PutFut {
+            store: self,
+            item: Some(item),
+            identifier: None,
+        }
+    }
+
+    /// Take the oldest item out of the store, waiting until there is one
+    pub fn get(&self) -> GetFut<'_, T> {
+        GetFut {
+            store: self,
+            filter: None,
+            identifier: None,
+        }
+    }
+
+    /// Take the oldest item that `filter` accepts, waiting until there is one
+    pub fn get_filtered(&self, filter: impl Fn(&T) -> bool + 'static) -> GetFut<'_, T> {
+        GetFut {
+            store: self,
+            filter: Some(Box::new(filter)),
+            identifier: None,
+        }
+    }
+
+    /// The number of items in the store
+    pub fn len(&self) -> usize {
+        self.inner.borrow().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().items.is_empty()
+    }
+
+    /// The maximum number of items (None if unbounded)
+    pub fn capacity(&self) -> Option<usize> {
+        self.inner.borrow().capacity
+    }
+
+    pub fn stats(&self) -> StoreStats {
+        self.inner.borrow().stats.clone()
+    }
+}
+
+pub struct PutFut<'a, T> {
+    store: &'a Store<T>,
+    item: Option<T>,
+    /// Set once we are in the waiter queue
+    identifier: Option<u64>,
+}
+
+impl<T> Unpin for PutFut<'_, T> {}
+
+impl<T> Future for PutFut<'_, T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        let mut inner = this.store.inner.borrow_mut();
+
+        if inner.putters.is_next(this.identifier) && inner.has_space() {
+            if let Some(identifier) = this.identifier.take() {
+                inner.putters.remove(identifier);
+            }
+
+            let item = this.item.take().expect("Polled after completion");
+            inner.items.push_back(item);
+            inner.stats.puts += 1;
+
+            inner.dispatch();
+            inner.update_stats();
+            Poll::Ready(())
+        } else {
+            inner.putters.register(&mut this.identifier, ctx.waker());
+            inner.update_stats();
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Drop for PutFut<'_, T> {
+    fn drop(&mut self) {
+        if let Some(identifier) = self.identifier.take() {
+            let mut inner = self.store.inner.borrow_mut();
+
+            // Pass on our turn, so the wakeup is not lost
+            if inner.putters.remove(identifier) && inner.has_space() {
+                inner.putters.wake_next();
+            }
+
+            inner.update_stats();
+        }
+    }
+}
+
+pub struct GetFut<'a, T> {
+    store: &'a Store<T>,
+    /// Moved into the store once we start waiting
+    filter: Option<Filter<T>>,
+    /// Set once we are waiting
+    identifier: Option<u64>,
+}
+
+impl<T> Unpin for GetFut<'_, T> {}
+
+impl<T> Future for GetFut<'_, T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<T> {
+        let this = &mut *self;
+        let inner = &mut *this.store.inner.borrow_mut();
+
+        let item = if let Some(identifier) = this.identifier {
+            let pos = inner
+                .getters
+                .iter()
+                .position(|g| g.identifier == identifier)
+                .expect("Getter vanished");
+
+            if inner.getters[pos].item.is_none() {
+                inner.getters[pos].waker = ctx.waker().clone();
+                return Poll::Pending;
+            }
+
+            this.identifier = None;
+            inner.getters.remove(pos).item.unwrap()
+        } else {
+            let filter = &this.filter;
+            let pos = inner
+                .items
+                .iter()
+                .position(|item| filter.as_ref().is_none_or(|filter| filter(item)));
+
+            let Some(pos) = pos else {
+                let identifier = inner.next_getter_id;
+                inner.next_getter_id += 1;
+
+                inner.getters.push(Getter {
+                    identifier,
+                    filter: this.filter.take(),
+                    waker: ctx.waker().clone(),
+                    item: None,
+                });
+                inner.update_stats();
+
+                this.identifier = Some(identifier);
+                return Poll::Pending;
+            };
+
+            let item = inner.items.remove(pos).unwrap();
+            inner.putters.wake_next();
+            item
+        };
+
+        inner.stats.gets += 1;
+        inner.update_stats();
+        Poll::Ready(item)
+    }
+}
+
+impl<T> Drop for GetFut<'_, T> {
+    fn drop(&mut self) {
+        let Some(identifier) = self.identifier.take() else {
+            return;
+        };
+
+        let inner = &mut *self.store.inner.borrow_mut();
+        let pos = inner
+            .getters
+            .iter()
+            .position(|g| g.identifier == identifier)
+            .expect("Getter vanished");
+
+        // Give the item we did not take to somebody else
+        if let Some(item) = inner.getters.remove(pos).item {
+            inner.items.push_front(item);
+            inner.dispatch();
+        }
+
+        inner.update_stats();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::Store;
+    use crate::time::Duration;
+
+    #[test]
+    fn filtered_get() {
+        crate::Runtime::new().block_on(async {
+            let store = Rc::new(Store::new(2));
+            let log = Rc::new(RefCell::new(vec![]));
+
+            for (name, parity) in [("even", 0), ("odd", 1)] {
+                let store = store.clone();
+                let log = log.clone();
+
+                crate::spawn(async move {
+                    for _ in 0..2 {
+                        let item = store.get_filtered(move |item| item % 2 == parity).await;
+                        log.borrow_mut()
+                            .push((name, item, crate::time::now().to_millis()));
+                    }
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(1)).await;
+
+            // 1 is handed to a waiting getter, but putting 2 has to wait
+            // until the odd getter comes back for 3
+            for item in [1, 3, 5, 2] {
+                store.put(item).await;
+            }
+            assert_eq!(crate::time::now().to_millis(), 1);
+            assert_eq!(store.len(), 1);
+
+            assert_eq!(store.get().await, 5);
+            assert_eq!(store.len(), 0);
+
+            // Let the even getter pick up its item
+            crate::time::sleep(Duration::from_millis(1)).await;
+
+            let stats = store.stats();
+            assert_eq!((stats.puts, stats.gets), (4, 4));
+            assert_eq!(stats.level.max(), 2.0);
+
+            assert_eq!(
+                *log.borrow(),
+                vec![("odd", 1, 1), ("odd", 3, 1), ("even", 2, 1)]
+            );
+        });
+    }
+}
@@ -1,27 +1,38 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
 use std::future::Future;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::task::Context;
+use std::time::SystemTime;
 
 use futures::task::waker_ref;
 
+use crate::deadlock::{Deadlock, WaitGraph};
+#[cfg(feature = "network")]
+use crate::network::IdAllocator;
+use crate::random::SeededRng;
 use crate::time::Timer;
-use crate::{RcWrapper, Task, TaskQueue, CONTEXT};
+use crate::{Priority, RcWrapper, Task, TaskQueue, CONTEXT};
 
 /// An event queue servers as an executor for the async tasks simulating the timed events
 pub struct Runtime {
     ready_tasks: Rc<RefCell<TaskQueue>>,
     timer: Rc<Timer>,
+    next_task_id: Rc<Cell<u64>>,
+    current_task: Rc<RefCell<Option<Rc<Task>>>>,
+    wait_graph: Rc<RefCell<WaitGraph>>,
+    rng: Rc<RefCell<SeededRng>>,
+    #[cfg(feature = "network")]
+    object_ids: Rc<IdAllocator>,
+    /// Changes whenever links are added, removed, fail, or change their latency
+    #[cfg(feature = "network")]
+    topology_version: Rc<Cell<u64>>,
 }
 
 impl Default for Runtime {
     fn default() -> Self {
-        let ready_tasks = Default::default();
-        Self {
-            ready_tasks,
-            timer: Rc::new(Timer::new()),
-        }
+        Self::with_timer(Timer::new(None))
     }
 }
 
@@ -54,6 +65,29 @@ impl Runtime {
         Self::default()
     }
 
+    /// Create a runtime whose start time corresponds to the given wall-clock time
+    ///
+    /// This allows converting simulated time into real timestamps and vice versa,
+    /// e.g., when replaying traces.
+    pub fn with_epoch(epoch: SystemTime) -> Self {
+        Self::with_timer(Timer::new(Some(epoch)))
+    }
+
+    fn with_timer(timer: Timer) -> Self {
+        Self {
+            ready_tasks: Default::default(),
+            timer: Rc::new(timer),
+            next_task_id: Default::default(),
+            current_task: Default::default(),
+            wait_graph: Default::default(),
+            rng: Default::default(),
+            #[cfg(feature = "network")]
+            object_ids: Default::default(),
+            #[cfg(feature = "network")]
+            topology_version: Default::default(),
+        }
+    }
+
     /// Set this runtime as the current asim context
     ///
     /// Can only be called when the runtime is not the active context yet
@@ -64,12 +98,17 @@ impl Runtime {
 
     /// Run all ready tasks
     /// Will return true if any task ran
+    ///
+    /// Tasks with higher priority run first
     pub fn execute_tasks(&self) -> bool {
-        let ready_tasks = {
+        let mut ready_tasks = {
             let mut tasks = self.ready_tasks.borrow_mut();
             std::mem::take(&mut *tasks)
         };
 
+        // This is a stable sort, so tasks of the same priority keep their order
+        ready_tasks.sort_by_key(|task| Reverse(task.get_priority()));
+
         if ready_tasks.is_empty() {
             return false;
         } else {
@@ -88,7 +127,15 @@ impl Runtime {
                 let waker = waker_ref(&wrapper);
                 let context = &mut Context::from_waker(&waker);
 
-                if future.as_mut().poll(context).is_pending() {
+                *self.current_task.borrow_mut() = Some(task.clone());
+                let result = future.as_mut().poll(context);
+                *self.current_task.borrow_mut() = None;
+
+                if result.is_pending() {
+                    // SyncMutex guards must not be held across await points
+                    #[cfg(debug_assertions)]
+                    task.check_no_sync_locks();
+
                     *fut_lock = Some(future);
                 }
             }
@@ -99,12 +146,41 @@ impl Runtime {
     }
 
     pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
-        let task = Rc::new(Task::new(future, self.ready_tasks.clone()));
-        self.ready_tasks.borrow_mut().push(task);
+        self.spawn_with_priority(Priority::default(), future);
+    }
+
+    /// Spawn a new task with the specified priority class
+    pub fn spawn_with_priority(
+        &self,
+        priority: Priority,
+        future: impl Future<Output = ()> + 'static,
+    ) {
+        spawn_task(
+            &self.ready_tasks,
+            &self.next_task_id,
+            None,
+            priority,
+            future,
+        );
+    }
+
+    /// Spawn a new task with a name that shows up in diagnostics
+    pub fn spawn_named(&self, name: impl Into<String>, future: impl Future<Output = ()> + 'static) {
+        spawn_task(
+            &self.ready_tasks,
+            &self.next_task_id,
+            Some(name.into()),
+            Priority::default(),
+            future,
+        );
     }
 
     /// Spawns a task and waits for it to complete
     ///
+    /// Panics if the simulation stalls before the task completes,
+    /// i.e., if there are no more tasks to run or timers to fire.
+    /// The panic message includes all deadlocks found.
+    ///
     /// Note: This cannot be called from within an asim context
     pub fn block_on(&self, future: impl Future<Output = ()> + 'static) {
         let done = Rc::new(RefCell::new(false));
@@ -117,12 +193,19 @@ impl Runtime {
             }
         };
 
-        let task = Rc::new(Task::new(future, self.ready_tasks.clone()));
-        self.ready_tasks.borrow_mut().push(task);
+        self.spawn(future);
 
         while !*done.borrow() {
-            self.execute_tasks();
-            self.timer.advance();
+            // Only move time forward once all tasks for the current time have run
+            if !self.execute_tasks() && !self.timer.advance() {
+                let mut message = String::from("Simulation stalled before the task completed");
+
+                for deadlock in self.find_deadlocks() {
+                    message += &format!("\n{deadlock}");
+                }
+
+                panic!("{message}");
+            }
         }
     }
 
@@ -137,24 +220,134 @@ impl Runtime {
         Handle {
             ready_tasks: self.ready_tasks.clone(),
             timer: self.timer.clone(),
+            next_task_id: self.next_task_id.clone(),
+            current_task: self.current_task.clone(),
+            wait_graph: self.wait_graph.clone(),
+            rng: self.rng.clone(),
+            #[cfg(feature = "network")]
+            object_ids: self.object_ids.clone(),
+            #[cfg(feature = "network")]
+            topology_version: self.topology_version.clone(),
         }
     }
 
     pub fn get_timer(&self) -> &Timer {
         &self.timer
     }
+
+    /// Find cycles of tasks that wait for each other
+    ///
+    /// See [`crate::deadlock`] for details
+    pub fn find_deadlocks(&self) -> Vec<Deadlock> {
+        self.wait_graph.borrow().find_deadlocks()
+    }
+
+    /// Reset the random number generator with the given seed
+    ///
+    /// See [`crate::random`] for details
+    pub fn set_seed(&self, seed: u64) {
+        *self.rng.borrow_mut() = SeededRng::new(seed);
+    }
+
+    /// The seed of the random number generator
+    pub fn get_seed(&self) -> u64 {
+        self.handle().get_seed()
+    }
 }
 
 #[derive(Clone)]
 pub struct Handle {
     ready_tasks: Rc<RefCell<TaskQueue>>,
     timer: Rc<Timer>,
+    next_task_id: Rc<Cell<u64>>,
+    current_task: Rc<RefCell<Option<Rc<Task>>>>,
+    wait_graph: Rc<RefCell<WaitGraph>>,
+    rng: Rc<RefCell<SeededRng>>,
+    #[cfg(feature = "network")]
+    object_ids: Rc<IdAllocator>,
+    /// Changes whenever links are added, removed, fail, or change their latency
+    #[cfg(feature = "network")]
+    topology_version: Rc<Cell<u64>>,
+}
+
+fn spawn_task(
+    ready_tasks: &Rc<RefCell<TaskQueue>>,
+    next_task_id: &Cell<u64>,
+    name: Option<String>,
+    priority: Priority,
+    future: impl Future<Output = ()> + 'static,
+) {
+    let identifier = next_task_id.get();
+    next_task_id.set(identifier + 1);
+
+    let task = Task::new(identifier, name, future, priority, ready_tasks.clone());
+    ready_tasks.borrow_mut().push(Rc::new(task));
 }
 
 impl Handle {
+    /// The task that is currently running, if any
+    pub fn current_task(&self) -> Option<Rc<Task>> {
+        self.current_task.borrow().clone()
+    }
+
+    /// Find cycles of tasks that wait for each other
+    ///
+    /// See [`crate::deadlock`] for details
+    pub fn find_deadlocks(&self) -> Vec<Deadlock> {
+        self.wait_graph.borrow().find_deadlocks()
+    }
+
+    pub(crate) fn get_wait_graph(&self) -> &Rc<RefCell<WaitGraph>> {
+        &self.wait_graph
+    }
+
+    pub(crate) fn get_rng(&self) -> &Rc<RefCell<SeededRng>> {
+        &self.rng
+    }
+
+    /// The seed of the random number generator
+    pub fn get_seed(&self) -> u64 {
+        self.rng.borrow().seed()
+    }
+
+    #[cfg(feature = "network")]
+    pub(crate) fn get_object_ids(&self) -> &IdAllocator {
+        &self.object_ids
+    }
+
+    #[cfg(feature = "network")]
+    pub(crate) fn get_topology_version(&self) -> &Cell<u64> {
+        &self.topology_version
+    }
+
     pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
-        let task = Rc::new(Task::new(future, self.ready_tasks.clone()));
-        self.ready_tasks.borrow_mut().push(task);
+        self.spawn_with_priority(Priority::default(), future);
+    }
+
+    /// Spawn a new task with the specified priority class
+    pub fn spawn_with_priority(
+        &self,
+        priority: Priority,
+        future: impl Future<Output = ()> + 'static,
+    ) {
+        spawn_task(
+            &self.ready_tasks,
+            &self.next_task_id,
+            None,
+            priority,
+            future,
+        );
+    }
+
+    /// Spawn a new task with a name that shows up in diagnostics
+    pub fn spawn_named(&self, name: impl Into<String>, future: impl Future<Output = ()> + 'static) {
+        spawn_task(
+            &self.ready_tasks,
+            &self.next_task_id,
+            Some(name.into()),
+            Priority::default(),
+            future,
+        );
     }
 
     /// Drops all queued events
@@ -166,3 +359,58 @@ impl Handle {
         &self.timer
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use std::time::UNIX_EPOCH;
+
+    use super::Runtime;
+    use crate::time::{Duration, Time, START_TIME};
+    use crate::Priority;
+
+    #[test]
+    fn priority_order() {
+        let runtime = Runtime::new();
+        let order = Rc::new(RefCell::new(vec![]));
+
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let order = order.clone();
+            runtime.spawn_with_priority(priority, async move {
+                crate::time::sleep(Duration::from_millis(10)).await;
+                order.borrow_mut().push(priority);
+            });
+        }
+
+        runtime.block_on(async {
+            crate::time::sleep(Duration::from_millis(20)).await;
+        });
+
+        assert_eq!(
+            *order.borrow(),
+            vec![Priority::High, Priority::Normal, Priority::Low]
+        );
+    }
+
+    #[test]
+    fn epoch() {
+        let epoch = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let runtime = Runtime::with_epoch(epoch);
+
+        runtime.block_on(async move {
+            crate::time::sleep(Duration::from_millis(1_500)).await;
+
+            let now = crate::time::now();
+            assert_eq!(
+                now.to_system_time(),
+                epoch + std::time::Duration::from_millis(1_500)
+            );
+            assert_eq!(now.to_rfc3339(), "2023-11-14T22:13:21.5Z");
+
+            assert_eq!(Time::from_system_time(epoch), Some(START_TIME));
+            assert_eq!(Time::from_system_time(UNIX_EPOCH), None);
+        });
+    }
+}
@@ -0,0 +1,184 @@
+/// Scoped tasks that can borrow from their environment
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+
+use crate::task::BoxFuture;
+
+struct ScopeInner<'env> {
+    spawned: Vec<BoxFuture<'env, ()>>,
+    waker: Option<Waker>,
+    joined: bool,
+}
+
+/// A handle to spawn tasks within a scope
+///
+/// Created by [`scope`]. Tasks spawned through this handle may borrow
+/// anything that outlives the scope.
+#[derive(Clone)]
+pub struct Scope<'env> {
+    inner: Rc<RefCell<ScopeInner<'env>>>,
+}
+
+impl<'env> Scope<'env> {
+    /// Spawn a new task within this scope
+    ///
+    /// The task will be joined before the scope completes.
+    pub fn spawn(&self, future: impl Future<Output = ()> + 'env) {
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.joined {
+            panic!("Scope has already been joined");
+        }
+
+        inner.spawned.push(Box::pin(future));
+
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Create a scope that allows spawning tasks that borrow from the enclosing future
+///
+/// The returned future completes once the future created by `func`
+/// and all tasks spawned in the scope have completed.
+///
+/// Note, scoped tasks are driven by the task awaiting the scope,
+/// so they share its priority and never run in parallel with it
+/// (in simulated time, they do run concurrently).
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// asim::Runtime::new().block_on(async {
+///     let counter = Cell::new(0);
+///     let counter = &counter;
+///
+///     asim::scope(|s| async move {
+///         for _ in 0..3 {
+///             s.spawn(async move {
+///                 counter.set(counter.get() + 1);
+///             });
+///         }
+///     })
+///     .await;
+///
+///     assert_eq!(counter.get(), 3);
+/// });
+/// ```
+pub fn scope<'env, F, Fut, R>(func: F) -> ScopeFut<'env, R>
+where
+    F: FnOnce(Scope<'env>) -> Fut,
+    Fut: Future<Output = R> + 'env,
+{
+    let scope = Scope {
+        inner: Rc::new(RefCell::new(ScopeInner {
+            spawned: vec![],
+            waker: None,
+            joined: false,
+        })),
+    };
+
+    let main = Box::pin(func(scope.clone()));
+
+    ScopeFut {
+        scope,
+        main: Some(main),
+        output: None,
+        children: FuturesUnordered::new(),
+    }
+}
+
+pub struct ScopeFut<'env, R> {
+    scope: Scope<'env>,
+    main: Option<BoxFuture<'env, R>>,
+    output: Option<R>,
+    children: FuturesUnordered<BoxFuture<'env, ()>>,
+}
+
+// The output is never pinned, so this is safe
+impl<R> Unpin for ScopeFut<'_, R> {}
+
+impl<R> Future for ScopeFut<'_, R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<R> {
+        let this = self.get_mut();
+        this.scope.inner.borrow_mut().waker = Some(ctx.waker().clone());
+
+        loop {
+            if let Some(main) = &mut this.main {
+                if let Poll::Ready(output) = main.as_mut().poll(ctx) {
+                    this.output = Some(output);
+                    this.main = None;
+                }
+            }
+
+            let spawned = std::mem::take(&mut this.scope.inner.borrow_mut().spawned);
+            this.children.extend(spawned);
+
+            while let Poll::Ready(Some(())) = this.children.poll_next_unpin(ctx) {}
+
+            // Children might have spawned more tasks while being polled
+            if this.scope.inner.borrow().spawned.is_empty() {
+                break;
+            }
+        }
+
+        if this.main.is_none() && this.children.is_empty() {
+            let mut inner = this.scope.inner.borrow_mut();
+            inner.joined = true;
+            inner.waker = None;
+
+            Poll::Ready(this.output.take().expect("Scope polled after completion"))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<R> Drop for ScopeFut<'_, R> {
+    fn drop(&mut self) {
+        // Tasks still queued cannot run anymore, and the waker would keep our task alive
+        let mut inner = self.scope.inner.borrow_mut();
+        inner.joined = true;
+        inner.waker = None;
+        inner.spawned.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::time::Duration;
+
+    #[test]
+    fn join_children() {
+        crate::Runtime::new().block_on(async {
+            let log = RefCell::new(vec![]);
+            let log = &log;
+
+            let result = super::scope(|s| async move {
+                for idx in (1..=3).rev() {
+                    s.spawn(async move {
+                        crate::time::sleep(Duration::from_millis(idx * 10)).await;
+                        log.borrow_mut().push(idx);
+                    });
+                }
+
+                42
+            })
+            .await;
+
+            assert_eq!(result, 42);
+            assert_eq!(*log.borrow(), vec![1, 2, 3]);
+            assert_eq!(crate::time::now().to_millis(), 30);
+        });
+    }
+}
@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::wait_queue::WaitQueue;
+use crate::deadlock::Resource;
+
+struct BarrierInner {
+    /// How many tasks are currently waiting
+    arrived: usize,
+    /// Incremented every time the barrier releases its tasks
+    generation: u64,
+    waiters: WaitQueue,
+}
+
+/// Lets a fixed number of tasks wait for each other, e.g., at the end of a computation phase
+///
+/// The barrier can be reused after all tasks have been released.
+pub struct Barrier {
+    num_tasks: usize,
+    inner: RefCell<BarrierInner>,
+}
+
+/// Returned once the barrier released a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    is_leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Exactly one task per generation is the leader (the last one to arrive)
+    pub fn is_leader(&self) -> bool {
+        self.is_leader
+    }
+}
+
+pub struct BarrierWaitFut<'a> {
+    barrier: &'a Barrier,
+    /// The generation we are waiting in, once we have arrived
+    generation: Option<u64>,
+    identifier: Option<u64>,
+}
+
+impl Barrier {
+    /// Create a barrier that releases tasks once `num_tasks` of them are waiting
+    #[track_caller]
+    pub fn new(num_tasks: usize) -> Self {
+        assert!(num_tasks > 0, "Barrier needs at least one task");
+
+        Self {
+            num_tasks,
+            inner: RefCell::new(BarrierInner {
+                arrived: 0,
+                generation: 0,
+                waiters: WaitQueue::new(Resource::new("Barrier")),
+            }),
+        }
+    }
+
+    /// Wait until all tasks reached the barrier
+    pub fn wait(&self) -> BarrierWaitFut<'_> {
+        BarrierWaitFut {
+            barrier: self,
+            generation: None,
+            identifier: None,
+        }
+    }
+}
+
+impl Future for BarrierWaitFut<'_> {
+    type Output = BarrierWaitResult;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<BarrierWaitResult> {
+        let this = &mut *self;
+        let mut inner = this.barrier.inner.borrow_mut();
+
+        let Some(generation) = this.generation else {
+            inner.arrived += 1;
+
+            if inner.arrived == this.barrier.num_tasks {
+                log::trace!("Barrier released {} tasks", inner.arrived);
+
+                inner.arrived = 0;
+                inner.generation += 1;
+                inner.waiters.wake_all();
+
+                return Poll::Ready(BarrierWaitResult { is_leader: true });
+            }
+
+            this.generation = Some(inner.generation);
+            inner.waiters.register(&mut this.identifier, ctx.waker());
+            return Poll::Pending;
+        };
+
+        if generation != inner.generation {
+            if let Some(identifier) = this.identifier.take() {
+                inner.waiters.remove(identifier);
+            }
+
+            this.generation = None;
+            Poll::Ready(BarrierWaitResult { is_leader: false })
+        } else {
+            inner.waiters.register(&mut this.identifier, ctx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for BarrierWaitFut<'_> {
+    fn drop(&mut self) {
+        let Some(identifier) = self.identifier.take() else {
+            return;
+        };
+
+        let mut inner = self.barrier.inner.borrow_mut();
+        inner.waiters.remove(identifier);
+
+        // We gave up before the barrier released us, so we no longer count as arrived
+        if self.generation == Some(inner.generation) {
+            inner.arrived -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::Barrier;
+    use crate::time::Duration;
+
+    #[test]
+    fn phases() {
+        crate::Runtime::new().block_on(async {
+            let barrier = Rc::new(Barrier::new(3));
+            let log = Rc::new(RefCell::new(vec![]));
+
+            for idx in 1..=3 {
+                let barrier = barrier.clone();
+                let log = log.clone();
+
+                crate::spawn(async move {
+                    for _ in 0..2 {
+                        crate::time::sleep(Duration::from_millis(idx)).await;
+                        let result = barrier.wait().await;
+                        log.borrow_mut()
+                            .push((crate::time::now().to_millis(), result.is_leader()));
+                    }
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(10)).await;
+
+            let log = log.borrow();
+            assert_eq!(log.len(), 6);
+            assert_eq!(log.iter().filter(|(_, leader)| *leader).count(), 2);
+            assert!(log[..3].iter().all(|(time, _)| *time == 3));
+            assert!(log[3..].iter().all(|(time, _)| *time == 6));
+        });
+    }
+}
@@ -0,0 +1,347 @@
+/// Multi-producer, multi-consumer channels where every receiver sees every message
+///
+/// Each channel keeps a bounded history of messages. Receivers that fall so far behind
+/// that messages were overwritten before they received them get a `Lagged` error
+/// and then continue with the oldest message still available.
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use crate::deadlock::{EndpointUser, Resource, Waiting};
+
+struct Inner<T> {
+    /// Messages with their sequence number
+    messages: VecDeque<(u64, T)>,
+    capacity: usize,
+    /// Sequence number of the next message to be sent
+    next_seq: u64,
+    num_senders: usize,
+    num_receivers: usize,
+    next_receiver_id: u64,
+    waiters: HashMap<u64, Waker>,
+    /// Represents all senders for deadlock detection
+    senders: Resource,
+}
+
+impl<T> Inner<T> {
+    fn oldest_seq(&self) -> u64 {
+        self.messages
+            .front()
+            .map(|(seq, _)| *seq)
+            .unwrap_or(self.next_seq)
+    }
+}
+
+/// Returned when sending to a channel without receivers
+///
+/// Contains the message that could not be sent
+pub struct SendError<T>(pub T);
+
+/// Returned by [`Receiver::recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// All senders are gone and all messages have been received
+    Closed,
+    /// The receiver fell behind and missed the specified number of messages
+    Lagged(u64),
+}
+
+/// Returned by [`Receiver::try_recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There is no new message right now
+    Empty,
+    /// All senders are gone and all messages have been received
+    Closed,
+    /// The receiver fell behind and missed the specified number of messages
+    Lagged(u64),
+}
+
+pub struct Sender<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+    user: EndpointUser,
+}
+
+impl<T: Clone> Sender<T> {
+    /// Send a message to all current receivers
+    ///
+    /// Returns how many receivers will see the message,
+    /// or an error if there are no receivers.
+    pub fn send(&self, msg: T) -> Result<usize, SendError<T>> {
+        self.user.touch();
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.num_receivers == 0 {
+            return Err(SendError(msg));
+        }
+
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        inner.messages.push_back((seq, msg));
+
+        if inner.messages.len() > inner.capacity {
+            inner.messages.pop_front();
+        }
+
+        for (_, waker) in inner.waiters.drain() {
+            waker.wake();
+        }
+
+        Ok(inner.num_receivers)
+    }
+
+    /// Create a new receiver that will see all messages sent from now on
+    pub fn subscribe(&self) -> Receiver<T> {
+        let next_seq = self.inner.borrow().next_seq;
+        Receiver::new(self.inner.clone(), next_seq)
+    }
+
+    /// The number of receivers that currently exist
+    pub fn receiver_count(&self) -> usize {
+        self.inner.borrow().num_receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let mut inner = self.inner.borrow_mut();
+        inner.num_senders += 1;
+
+        Self {
+            inner: self.inner.clone(),
+            user: EndpointUser::new(&inner.senders),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.num_senders -= 1;
+
+        if inner.num_senders == 0 {
+            for (_, waker) in inner.waiters.drain() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+    identifier: u64,
+    /// Sequence number of the next message this receiver expects
+    next_seq: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    fn new(inner: Rc<RefCell<Inner<T>>>, next_seq: u64) -> Self {
+        let identifier = {
+            let mut inner = inner.borrow_mut();
+            inner.num_receivers += 1;
+
+            let identifier = inner.next_receiver_id;
+            inner.next_receiver_id += 1;
+            identifier
+        };
+
+        Self {
+            inner,
+            identifier,
+            next_seq,
+        }
+    }
+
+    /// Receive the next message
+    #[must_use]
+    pub fn recv(&mut self) -> RecvFut<'_, T> {
+        RecvFut {
+            receiver: self,
+            waiting: None,
+        }
+    }
+
+    /// Receive the next message, if there is one
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let inner = self.inner.clone();
+        let inner = inner.borrow();
+
+        let oldest = inner.oldest_seq();
+        if self.next_seq < oldest {
+            let missed = oldest - self.next_seq;
+            log::trace!("Broadcast receiver lagged behind by {missed} messages");
+
+            self.next_seq = oldest;
+            return Err(TryRecvError::Lagged(missed));
+        }
+
+        if self.next_seq < inner.next_seq {
+            let (_, msg) = &inner.messages[(self.next_seq - oldest) as usize];
+            self.next_seq += 1;
+            Ok(msg.clone())
+        } else if inner.num_senders == 0 {
+            Err(TryRecvError::Closed)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+}
+
+impl<T: Clone> Clone for Receiver<T> {
+    /// The new receiver starts at the same position as this one
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone(), self.next_seq)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.num_receivers -= 1;
+        inner.waiters.remove(&self.identifier);
+    }
+}
+
+pub struct RecvFut<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    waiting: Option<Waiting>,
+}
+
+impl<T: Clone> Future for RecvFut<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        let result = match this.receiver.try_recv() {
+            Ok(msg) => Ok(msg),
+            Err(TryRecvError::Lagged(missed)) => Err(RecvError::Lagged(missed)),
+            Err(TryRecvError::Closed) => Err(RecvError::Closed),
+            Err(TryRecvError::Empty) => {
+                let mut inner = this.receiver.inner.borrow_mut();
+                inner
+                    .waiters
+                    .insert(this.receiver.identifier, ctx.waker().clone());
+
+                if this.waiting.is_none() {
+                    this.waiting = Some(Waiting::new(&inner.senders));
+                }
+
+                return Poll::Pending;
+            }
+        };
+
+        this.waiting = None;
+        Poll::Ready(result)
+    }
+}
+
+/// Create a broadcast channel that keeps the last `capacity` messages
+#[track_caller]
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "Channel capacity must be at least one");
+    let senders = Resource::new("broadcast::Sender");
+
+    let inner = Rc::new(RefCell::new(Inner {
+        messages: VecDeque::with_capacity(capacity),
+        capacity,
+        next_seq: 0,
+        num_senders: 1,
+        num_receivers: 0,
+        next_receiver_id: 0,
+        waiters: HashMap::new(),
+        senders,
+    }));
+
+    let receiver = Receiver::new(inner.clone(), 0);
+    let user = EndpointUser::new(&senders);
+    (Sender { inner, user }, receiver)
+}
+
+impl<T> std::fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> std::fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "channel has no receivers")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+impl std::fmt::Display for RecvError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Closed => write!(fmt, "channel closed"),
+            Self::Lagged(missed) => write!(fmt, "receiver lagged behind by {missed} messages"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+impl std::fmt::Display for TryRecvError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(fmt, "channel empty"),
+            Self::Closed => write!(fmt, "channel closed"),
+            Self::Lagged(missed) => write!(fmt, "receiver lagged behind by {missed} messages"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, RecvError, TryRecvError};
+    use crate::time::Duration;
+
+    #[test]
+    fn every_receiver_sees_every_message() {
+        crate::Runtime::new().block_on(async {
+            let (sender, mut receiver1) = channel(4);
+            let mut receiver2 = sender.subscribe();
+
+            crate::spawn(async move {
+                for idx in 0..3 {
+                    crate::time::sleep(Duration::from_millis(1)).await;
+                    assert_eq!(sender.send(idx).unwrap(), 2);
+                }
+            });
+
+            for idx in 0..3 {
+                assert_eq!(receiver1.recv().await, Ok(idx));
+            }
+            assert_eq!(receiver1.recv().await, Err(RecvError::Closed));
+
+            for idx in 0..3 {
+                assert_eq!(receiver2.try_recv(), Ok(idx));
+            }
+            assert_eq!(receiver2.try_recv(), Err(TryRecvError::Closed));
+        });
+    }
+
+    #[test]
+    fn lagged() {
+        let (sender, mut receiver) = channel(2);
+
+        for idx in 0..5 {
+            sender.send(idx).unwrap();
+        }
+
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Lagged(3)));
+        assert_eq!(receiver.try_recv(), Ok(3));
+        assert_eq!(receiver.try_recv(), Ok(4));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+        drop(receiver);
+        assert!(sender.send(5).is_err());
+    }
+}
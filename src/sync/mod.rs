@@ -1,12 +1,28 @@
 /// Concurrency primitives for asim
+pub mod broadcast;
 pub mod mpsc;
+pub mod watch;
 
 mod mutex;
-pub use mutex::{Condvar, LockGuard, Mutex};
+pub use mutex::{Condvar, LockGuard, Mutex, OwnedLockGuard, WaitTimeoutResult};
 
 mod sync_mutex;
 pub use sync_mutex::{SyncCondvar, SyncLockGuard, SyncMutex};
 
+mod rwlock;
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+mod semaphore;
+pub use semaphore::{Semaphore, SemaphorePermit, TryAcquireError};
+
+mod barrier;
+pub use barrier::{Barrier, BarrierWaitResult};
+
+mod stats;
+pub use stats::{CondvarStats, Histogram, LockStats};
+
+pub(crate) mod wait_queue;
+
 pub use tokio::sync::{oneshot, Notify};
 
 pub use std::sync::atomic;
@@ -1,75 +1,569 @@
-use parking_lot::Mutex;
-
+/// Multi-producer, single-consumer channels
+///
+/// Similar to tokio's mpsc module, there are bounded channels, where sending
+/// waits until there is space, and unbounded channels, where sending never blocks.
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::rc::Rc;
 use std::task::{Context, Poll, Waker};
 
-#[derive(Default)]
+use futures::Stream;
+
+use super::wait_queue::WaitQueue;
+use crate::deadlock::{EndpointUser, Resource, Waiting};
+
 struct Inner<T> {
-    messages: Vec<T>,
-    waker: Option<Waker>,
+    messages: VecDeque<T>,
+    /// None if unbounded
+    capacity: Option<usize>,
+    receiver_waker: Option<Waker>,
+    /// Set while the receiver waits for the senders
+    receiver_waiting: Option<Waiting>,
+    /// Senders waiting for space (i.e., for the receiver), in the order they started waiting
+    send_waiters: WaitQueue,
+    /// Represents all senders for deadlock detection
+    senders: Resource,
+    num_senders: usize,
+    closed: bool,
+}
+
+impl<T> Inner<T> {
+    fn has_space(&self) -> bool {
+        match self.capacity {
+            Some(capacity) => self.messages.len() < capacity,
+            None => true,
+        }
+    }
+
+    fn push(&mut self, msg: T) {
+        self.messages.push_back(msg);
+
+        if let Some(waker) = self.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_next_sender(&self) {
+        self.send_waiters.wake_next();
+    }
+}
+
+/// Returned when sending to a channel whose receiver is gone
+///
+/// Contains the message that could not be sent
+pub struct SendError<T>(pub T);
+
+/// Returned by [`Sender::try_send`]
+pub enum TrySendError<T> {
+    /// The channel has no space left
+    Full(T),
+    /// The receiver is gone
+    Closed(T),
 }
 
+/// Returned by [`Receiver::try_recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There are currently no messages
+    Empty,
+    /// There are no messages and all senders are gone
+    Disconnected,
+}
+
+/// Sends messages to a bounded channel
 pub struct Sender<T> {
-    inner: Arc<Mutex<Inner<T>>>,
+    inner: Rc<RefCell<Inner<T>>>,
+    user: EndpointUser,
+}
+
+/// Sends messages to an unbounded channel
+pub struct UnboundedSender<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+    user: EndpointUser,
 }
 
 impl<T> Sender<T> {
-    pub fn send(&self, msg: T) {
-        let mut inner = self.inner.lock();
-        inner.messages.push(msg);
+    /// Send a message, waiting until the channel has space
+    ///
+    /// Senders are served in the order they started waiting.
+    /// Fails if the receiver has been closed or dropped.
+    #[must_use]
+    pub fn send(&self, msg: T) -> SendFut<'_, T> {
+        SendFut {
+            sender: self,
+            message: Some(msg),
+            identifier: None,
+        }
+    }
+
+    /// Send a message if the channel has space right now
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        self.user.touch();
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.closed {
+            Err(TrySendError::Closed(msg))
+        } else if !inner.has_space() || !inner.send_waiters.is_empty() {
+            Err(TrySendError::Full(msg))
+        } else {
+            inner.push(msg);
+            Ok(())
+        }
+    }
+
+    /// Has the receiver been closed or dropped?
+    pub fn is_closed(&self) -> bool {
+        self.inner.borrow().closed
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Send a message
+    ///
+    /// Fails if the receiver has been closed or dropped
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.user.touch();
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.closed {
+            Err(SendError(msg))
+        } else {
+            inner.push(msg);
+            Ok(())
+        }
+    }
+
+    /// Has the receiver been closed or dropped?
+    pub fn is_closed(&self) -> bool {
+        self.inner.borrow().closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let mut inner = self.inner.borrow_mut();
+        inner.num_senders += 1;
+
+        Self {
+            inner: self.inner.clone(),
+            user: EndpointUser::new(&inner.senders),
+        }
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        let mut inner = self.inner.borrow_mut();
+        inner.num_senders += 1;
 
-        if let Some(waker) = inner.waker.take() {
+        Self {
+            inner: self.inner.clone(),
+            user: EndpointUser::new(&inner.senders),
+        }
+    }
+}
+
+fn drop_sender<T>(inner: &RefCell<Inner<T>>) {
+    let mut inner = inner.borrow_mut();
+    inner.num_senders -= 1;
+
+    // Let the receiver know there will be no more messages
+    if inner.num_senders == 0 {
+        if let Some(waker) = inner.receiver_waker.take() {
             waker.wake();
         }
     }
 }
 
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.inner);
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.inner);
+    }
+}
+
+pub struct SendFut<'a, T> {
+    sender: &'a Sender<T>,
+    message: Option<T>,
+    /// Set once we are in the waiter queue
+    identifier: Option<u64>,
+}
+
+impl<T> Unpin for SendFut<'_, T> {}
+
+impl<T> Future for SendFut<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        this.sender.user.touch();
+        let mut inner = this.sender.inner.borrow_mut();
+
+        if inner.closed {
+            if let Some(identifier) = this.identifier.take() {
+                inner.send_waiters.remove(identifier);
+            }
+
+            let msg = this.message.take().expect("Polled after completion");
+            return Poll::Ready(Err(SendError(msg)));
+        }
+
+        if inner.send_waiters.is_next(this.identifier) && inner.has_space() {
+            if let Some(identifier) = this.identifier.take() {
+                inner.send_waiters.remove(identifier);
+            }
+
+            let msg = this.message.take().expect("Polled after completion");
+            inner.push(msg);
+
+            // There might be space for the next waiter as well
+            if inner.has_space() {
+                inner.wake_next_sender();
+            }
+
+            return Poll::Ready(Ok(()));
+        }
+
+        inner
+            .send_waiters
+            .register(&mut this.identifier, ctx.waker());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for SendFut<'_, T> {
+    fn drop(&mut self) {
+        // Make sure a cancelled send does not block the ones queued after it
+        if let Some(identifier) = self.identifier.take() {
+            let mut inner = self.sender.inner.borrow_mut();
+
+            if inner.send_waiters.remove(identifier) && inner.has_space() {
+                inner.wake_next_sender();
+            }
+        }
+    }
+}
+
+/// Receives messages from a bounded or unbounded channel
 pub struct Receiver<T> {
-    inner: Arc<Mutex<Inner<T>>>,
+    inner: Rc<RefCell<Inner<T>>>,
+    user: EndpointUser,
 }
 
 impl<T> Receiver<T> {
+    /// Receive the next message
+    ///
+    /// Returns None once the channel is empty and all senders are gone
+    /// (or the receiver has been closed)
     #[must_use]
-    pub fn recv(&self) -> GetFut<T> {
-        GetFut {
-            inner: self.inner.clone(),
+    pub fn recv(&self) -> RecvFut<'_, T> {
+        RecvFut { receiver: self }
+    }
+
+    /// Receive the next message, if there is one
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.user.touch();
+        let mut inner = self.inner.borrow_mut();
+
+        if let Some(msg) = inner.messages.pop_front() {
+            inner.wake_next_sender();
+            Ok(msg)
+        } else if inner.num_senders == 0 || inner.closed {
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
         }
     }
-}
 
-pub struct GetFut<T> {
-    inner: Arc<Mutex<Inner<T>>>,
-}
+    /// Prevent any further messages from being sent
+    ///
+    /// Messages that are already in the channel can still be received
+    pub fn close(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.closed = true;
+        inner.send_waiters.wake_all();
+    }
 
-impl<T> Future for GetFut<T> {
-    type Output = Vec<T>;
+    /// The number of messages currently in the channel
+    pub fn len(&self) -> usize {
+        self.inner.borrow().messages.len()
+    }
 
-    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Vec<T>> {
-        let mut lock = self.inner.lock();
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().messages.is_empty()
+    }
 
-        if lock.messages.is_empty() {
-            lock.waker = Some(ctx.waker().clone());
-            Poll::Pending
+    fn poll_recv(&self, ctx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.user.touch();
+        let mut inner = self.inner.borrow_mut();
+
+        if let Some(msg) = inner.messages.pop_front() {
+            inner.receiver_waiting = None;
+            inner.wake_next_sender();
+            Poll::Ready(Some(msg))
+        } else if inner.num_senders == 0 || inner.closed {
+            inner.receiver_waiting = None;
+            Poll::Ready(None)
         } else {
-            let mut messages = vec![];
-            std::mem::swap(&mut messages, &mut lock.messages);
-            Poll::Ready(messages)
+            inner.receiver_waker = Some(ctx.waker().clone());
+
+            if inner.receiver_waiting.is_none() {
+                inner.receiver_waiting = Some(Waiting::new(&inner.senders));
+            }
+
+            Poll::Pending
         }
     }
 }
 
-pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-    let inner = Arc::new(Mutex::new(Inner {
-        messages: vec![],
-        waker: None,
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+
+        let messages = {
+            let mut inner = self.inner.borrow_mut();
+            inner.receiver_waiting = None;
+            std::mem::take(&mut inner.messages)
+        };
+
+        // Messages may hold senders of this channel, so drop them after releasing the borrow
+        drop(messages);
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(ctx)
+    }
+}
+
+pub struct RecvFut<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Future for RecvFut<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_recv(ctx)
+    }
+}
+
+impl<T> Drop for RecvFut<'_, T> {
+    fn drop(&mut self) {
+        self.receiver.inner.borrow_mut().receiver_waiting = None;
+    }
+}
+
+/// Returns the channel together with the endpoint users for a sender and the receiver
+#[track_caller]
+fn new_channel<T>(capacity: Option<usize>) -> (Rc<RefCell<Inner<T>>>, EndpointUser, EndpointUser) {
+    let receiver = Resource::new("mpsc::Receiver");
+    let senders = Resource::new("mpsc::Sender");
+
+    let inner = Rc::new(RefCell::new(Inner {
+        messages: VecDeque::new(),
+        capacity,
+        receiver_waker: None,
+        receiver_waiting: None,
+        send_waiters: WaitQueue::new(receiver),
+        senders,
+        num_senders: 1,
+        closed: false,
     }));
 
+    (
+        inner,
+        EndpointUser::new(&senders),
+        EndpointUser::new(&receiver),
+    )
+}
+
+/// Create a channel that holds at most `capacity` messages
+///
+/// Sending to a full channel waits until the receiver made space
+#[track_caller]
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "Channel capacity must be at least one");
+    let (inner, sender_user, receiver_user) = new_channel(Some(capacity));
+
     (
         Sender {
             inner: inner.clone(),
+            user: sender_user,
+        },
+        Receiver {
+            inner,
+            user: receiver_user,
+        },
+    )
+}
+
+/// Create a channel without a limit on how many messages it can hold
+#[track_caller]
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let (inner, sender_user, receiver_user) = new_channel(None);
+
+    (
+        UnboundedSender {
+            inner: inner.clone(),
+            user: sender_user,
+        },
+        Receiver {
+            inner,
+            user: receiver_user,
         },
-        Receiver { inner },
     )
 }
+
+impl<T> std::fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> std::fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "channel closed")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+impl<T> std::fmt::Debug for TrySendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full(_) => write!(fmt, "Full(..)"),
+            Self::Closed(_) => write!(fmt, "Closed(..)"),
+        }
+    }
+}
+
+impl<T> std::fmt::Display for TrySendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full(_) => write!(fmt, "no available capacity"),
+            Self::Closed(_) => write!(fmt, "channel closed"),
+        }
+    }
+}
+
+impl<T> std::error::Error for TrySendError<T> {}
+
+impl std::fmt::Display for TryRecvError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(fmt, "channel empty"),
+            Self::Disconnected => write!(fmt, "channel disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use futures::StreamExt;
+
+    use super::{channel, unbounded_channel, TryRecvError, TrySendError};
+    use crate::time::Duration;
+
+    #[test]
+    fn backpressure() {
+        crate::Runtime::new().block_on(async {
+            let (sender, receiver) = channel(2);
+            let sent = Rc::new(RefCell::new(vec![]));
+
+            {
+                let sent = sent.clone();
+                crate::spawn(async move {
+                    for idx in 0..4 {
+                        sender.send(idx).await.unwrap();
+                        sent.borrow_mut()
+                            .push((idx, crate::time::now().to_millis()));
+                    }
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(10)).await;
+            assert!(matches!(receiver.try_recv(), Ok(0)));
+
+            let mut received = vec![0];
+            while let Some(msg) = receiver.recv().await {
+                received.push(msg);
+            }
+
+            assert_eq!(received, vec![0, 1, 2, 3]);
+            assert_eq!(*sent.borrow(), vec![(0, 0), (1, 0), (2, 10), (3, 10)]);
+        });
+    }
+
+    #[test]
+    fn drop_messages_holding_senders() {
+        struct Message {
+            _sender: super::UnboundedSender<Message>,
+        }
+
+        let (sender, receiver) = unbounded_channel();
+        let _sender = sender.clone();
+        sender.send(Message { _sender }).unwrap();
+        drop(sender);
+
+        drop(receiver);
+    }
+
+    #[test]
+    fn try_send_and_recv() {
+        let (sender, receiver) = channel(1);
+
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        assert!(sender.try_send(1).is_ok());
+        assert!(matches!(sender.try_send(2), Err(TrySendError::Full(2))));
+        assert_eq!(receiver.try_recv(), Ok(1));
+
+        drop(sender);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn close() {
+        let (sender, receiver) = unbounded_channel();
+        let sender2 = sender.clone();
+
+        sender.send(1).unwrap();
+        receiver.close();
+
+        assert!(sender2.send(2).is_err());
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn stream() {
+        crate::Runtime::new().block_on(async {
+            let (sender, receiver) = unbounded_channel();
+
+            crate::spawn(async move {
+                for idx in 0..3 {
+                    sender.send(idx).unwrap();
+                    crate::time::sleep(Duration::from_millis(1)).await;
+                }
+            });
+
+            let received: Vec<_> = receiver.collect().await;
+            assert_eq!(received, vec![0, 1, 2]);
+        });
+    }
+}
@@ -6,25 +6,62 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::task::{Context, Poll, Waker};
 
-pub struct MutexInner {
+use super::stats::{CondvarStats, LockStats, LockStatsRecorder};
+use super::wait_queue::WaitQueue;
+use crate::deadlock::{self, Resource};
+use crate::time::{Duration, SleepFut, Time};
+
+struct MutexInner {
     is_locked: bool,
-    next_waiter_id: u32,
+    waiters: WaitQueue,
+    /// The task holding the lock, for deadlock detection
+    holder: Option<u64>,
+    stats: Option<LockStatsRecorder>,
+    /// When the current holder acquired the lock (only tracked with stats enabled)
+    locked_since: Option<Time>,
 }
 
-type Waiters = Vec<(u32, Waker)>;
-
 /// A Mutex that has an async lock function
 /// Useful if you want to hold a lock while waiting for some other task to complete
+///
+/// The lock is handed out in strict FIFO order of the (first) call to poll,
+/// and it is safe to drop a pending lock future at any time.
 pub struct Mutex<T> {
     data: RefCell<T>,
     inner: RefCell<MutexInner>,
-    waiters: RefCell<Waiters>,
 }
 
 type CondWaiters = Vec<(Rc<AtomicBool>, Waker)>;
 
 pub struct Condvar {
     waiters: Rc<RefCell<CondWaiters>>,
+    stats: Option<Rc<RefCell<CondvarStats>>>,
+}
+
+/// Records the duration of a condition variable wait once it completes
+struct CondWaitRecorder {
+    stats: Rc<RefCell<CondvarStats>>,
+    start: Time,
+}
+
+impl CondWaitRecorder {
+    fn new(stats: &Option<Rc<RefCell<CondvarStats>>>) -> Option<Self> {
+        stats.as_ref().map(|stats| Self {
+            stats: stats.clone(),
+            start: crate::time::now(),
+        })
+    }
+
+    fn finish(&self, timed_out: bool) {
+        let mut stats = self.stats.borrow_mut();
+        stats.waits += 1;
+        if timed_out {
+            stats.timeouts += 1;
+        }
+
+        let elapsed = crate::time::now().saturating_duration_since(self.start);
+        stats.wait_times.record(elapsed);
+    }
 }
 
 pub struct CondWait<'a, T> {
@@ -32,18 +69,60 @@ pub struct CondWait<'a, T> {
     lock_future: RefCell<Option<LockFuture<'a, T>>>,
     woken: Rc<AtomicBool>,
     waiters: Rc<RefCell<CondWaiters>>,
+    recorder: Option<CondWaitRecorder>,
+}
+
+pub struct CondTimeoutWait<'a, T> {
+    mutex: &'a Mutex<T>,
+    lock_future: Option<LockFuture<'a, T>>,
+    sleep_fut: SleepFut,
+    timed_out: bool,
+    /// Set once the future returned the lock
+    done: bool,
+    woken: Rc<AtomicBool>,
+    waiters: Rc<RefCell<CondWaiters>>,
+    recorder: Option<CondWaitRecorder>,
+}
+
+/// Whether a timed wait on a condition variable returned because of a timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitTimeoutResult(pub(super) bool);
+
+impl WaitTimeoutResult {
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
 }
 
 pub struct LockFuture<'a, T> {
-    identifier: u32,
+    /// Set once we are in the waiter queue
+    identifier: Option<u64>,
     mutex: &'a Mutex<T>,
 }
 
+pub struct LockTimeoutFuture<'a, T> {
+    lock_future: LockFuture<'a, T>,
+    sleep_fut: SleepFut,
+}
+
+pub struct OwnedLockFuture<T> {
+    identifier: Option<u64>,
+    mutex: Option<Rc<Mutex<T>>>,
+}
+
 pub struct LockGuard<'a, T> {
     data: RefMut<'a, T>,
     mutex: &'a Mutex<T>,
 }
 
+/// A lock guard that keeps the mutex alive and can, thus, be moved into other tasks
+///
+/// For deadlock detection, the lock counts as held by the task that most recently
+/// accessed the guard, and by no task before the guard is accessed for the first time.
+pub struct OwnedLockGuard<T> {
+    mutex: Rc<Mutex<T>>,
+}
+
 impl<'a, T> LockGuard<'a, T> {
     fn into_mutex(self) -> &'a Mutex<T> {
         self.mutex
@@ -52,41 +131,176 @@ impl<'a, T> LockGuard<'a, T> {
 
 impl<T> Drop for LockGuard<'_, T> {
     fn drop(&mut self) {
-        let mut inner = self.mutex.inner.borrow_mut();
-        inner.is_locked = false;
+        self.mutex.unlock();
+    }
+}
 
-        let waiters = self.mutex.waiters.borrow_mut();
-        if !waiters.is_empty() {
-            waiters[0].1.wake_by_ref();
-        }
+impl<T> Drop for OwnedLockGuard<T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
     }
 }
 
 impl<T> Mutex<T> {
+    #[track_caller]
     pub fn new(data: T) -> Self {
         Self {
             data: RefCell::new(data),
             inner: RefCell::new(MutexInner {
                 is_locked: false,
-                next_waiter_id: 0,
+                waiters: WaitQueue::new(Resource::new("Mutex")),
+                holder: None,
+                stats: None,
+                locked_since: None,
             }),
-            waiters: RefCell::new(vec![]),
         }
     }
 
-    pub fn lock(&self) -> LockFuture<'_, T> {
-        let mut inner = self.inner.borrow_mut();
-        let identifier = inner.next_waiter_id;
-        inner.next_waiter_id += 1;
+    /// Create a mutex that records contention statistics in simulated time
+    #[track_caller]
+    pub fn with_stats(data: T) -> Self {
+        let mutex = Self::new(data);
+        mutex.inner.borrow_mut().stats = Some(LockStatsRecorder::default());
+        mutex
+    }
+
+    /// The statistics recorded so far
+    ///
+    /// Returns None if the mutex was not created using `with_stats`
+    pub fn stats(&self) -> Option<LockStats> {
+        self.inner
+            .borrow()
+            .stats
+            .as_ref()
+            .map(|stats| stats.get().clone())
+    }
 
+    pub fn lock(&self) -> LockFuture<'_, T> {
         LockFuture {
-            identifier,
+            identifier: None,
             mutex: self,
         }
     }
+
+    /// Acquire the lock if that is possible without waiting
+    pub fn try_lock(&self) -> Option<LockGuard<'_, T>> {
+        if self.try_acquire(&mut None) {
+            Some(LockGuard {
+                data: self.data.borrow_mut(),
+                mutex: self,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Wait for the lock for at most the specified duration
+    ///
+    /// Returns None if the lock could not be acquired in time
+    pub fn lock_timeout(&self, timeout: Duration) -> LockTimeoutFuture<'_, T> {
+        LockTimeoutFuture {
+            lock_future: self.lock(),
+            sleep_fut: crate::time::sleep(timeout),
+        }
+    }
+
+    /// Like `lock` but returns a guard that is not bound to the lifetime of the mutex
+    pub fn lock_owned(self: Rc<Self>) -> OwnedLockFuture<T> {
+        OwnedLockFuture {
+            identifier: None,
+            mutex: Some(self),
+        }
+    }
+
+    /// Is the lock currently held by anybody?
+    pub fn is_locked(&self) -> bool {
+        self.inner.borrow().is_locked
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Take the lock if it is free and it is our turn
+    fn try_acquire(&self, identifier: &mut Option<u64>) -> bool {
+        let inner = &mut *self.inner.borrow_mut();
+
+        if inner.is_locked || !inner.waiters.is_next(*identifier) {
+            return false;
+        }
+
+        inner.is_locked = true;
+        inner.holder = deadlock::acquired(inner.waiters.resource());
+
+        if let Some(stats) = &mut inner.stats {
+            inner.locked_since = Some(stats.acquired(*identifier));
+        }
+
+        if let Some(identifier) = identifier.take() {
+            inner.waiters.remove(identifier);
+        }
+
+        true
+    }
+
+    fn register_waiter(&self, identifier: &mut Option<u64>, waker: &Waker) {
+        let mut inner = self.inner.borrow_mut();
+        inner.waiters.register(identifier, waker);
+
+        if let Some(stats) = &mut inner.stats {
+            stats.waiting(identifier.unwrap());
+        }
+    }
+
+    fn cancel_waiter(&self, identifier: &mut Option<u64>) {
+        if let Some(identifier) = identifier.take() {
+            let mut inner = self.inner.borrow_mut();
+
+            if let Some(stats) = &mut inner.stats {
+                stats.cancelled(identifier);
+            }
+
+            // We might have been woken up already, so pass on our turn
+            if inner.waiters.remove(identifier) && !inner.is_locked {
+                inner.waiters.wake_next();
+            }
+        }
+    }
+
+    /// Make the current task the holder of the lock, as owned guards can move between tasks
+    fn touch_holder(&self) {
+        let Some(current) = crate::current_task() else {
+            return;
+        };
+
+        let inner = &mut *self.inner.borrow_mut();
+        if inner.holder != Some(current.get_identifier()) {
+            deadlock::released(inner.waiters.resource(), inner.holder.take());
+            inner.holder = deadlock::acquired(inner.waiters.resource());
+        }
+    }
+
+    /// The task that acquired an owned guard might not be the one holding it
+    fn clear_holder(&self) {
+        let inner = &mut *self.inner.borrow_mut();
+        deadlock::released(inner.waiters.resource(), inner.holder.take());
+    }
+
+    fn unlock(&self) {
+        let inner = &mut *self.inner.borrow_mut();
+        inner.is_locked = false;
+        deadlock::released(inner.waiters.resource(), inner.holder.take());
+
+        if let (Some(stats), Some(since)) = (&mut inner.stats, inner.locked_since.take()) {
+            stats.released(since);
+        }
+
+        inner.waiters.wake_next();
+    }
 }
 
 impl<T: Default> Default for Mutex<T> {
+    #[track_caller]
     fn default() -> Self {
         Self::new(T::default())
     }
@@ -106,55 +320,111 @@ impl<T> DerefMut for LockGuard<'_, T> {
     }
 }
 
+impl<T> OwnedLockGuard<T> {
+    /// Get the mutex this guard belongs to
+    pub fn mutex(&self) -> &Rc<Mutex<T>> {
+        &self.mutex
+    }
+}
+
+impl<T> Deref for OwnedLockGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.mutex.touch_holder();
+
+        // SAFETY: We hold the lock, so nobody else can access the data
+        unsafe { &*self.mutex.data.as_ptr() }
+    }
+}
+
+impl<T> DerefMut for OwnedLockGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.mutex.touch_holder();
+
+        // SAFETY: We hold the lock, so nobody else can access the data
+        unsafe { &mut *self.mutex.data.as_ptr() }
+    }
+}
+
 impl<'a, T> Future for LockFuture<'a, T> {
     type Output = LockGuard<'a, T>;
 
-    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<LockGuard<'a, T>> {
-        let mut inner = self.mutex.inner.borrow_mut();
-
-        if !inner.is_locked {
-            inner.is_locked = true;
-            let mut waiters = self.mutex.waiters.borrow_mut();
-            for (idx, (id, _)) in waiters.iter().enumerate() {
-                if *id == self.identifier {
-                    waiters.remove(idx);
-                    break;
-                }
-            }
-
-            let data = self.mutex.data.borrow_mut();
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<LockGuard<'a, T>> {
+        let this = &mut *self;
 
+        if this.mutex.try_acquire(&mut this.identifier) {
             Poll::Ready(LockGuard {
-                data,
-                mutex: self.mutex,
+                data: this.mutex.data.borrow_mut(),
+                mutex: this.mutex,
             })
         } else {
-            let mut waiters = self.mutex.waiters.borrow_mut();
-            let mut found = false;
+            this.mutex
+                .register_waiter(&mut this.identifier, ctx.waker());
+            Poll::Pending
+        }
+    }
+}
 
-            for (id, _) in waiters.iter() {
-                if *id == self.identifier {
-                    found = true;
-                    break;
-                }
-            }
+impl<T> Drop for LockFuture<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.cancel_waiter(&mut self.identifier);
+    }
+}
 
-            if !found {
-                waiters.push((self.identifier, ctx.waker().clone()));
-            }
+impl<'a, T> Future for LockTimeoutFuture<'a, T> {
+    type Output = Option<LockGuard<'a, T>>;
 
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<LockGuard<'a, T>>> {
+        if let Poll::Ready(guard) = Pin::new(&mut self.lock_future).poll(ctx) {
+            Poll::Ready(Some(guard))
+        } else if Pin::new(&mut self.sleep_fut).poll(ctx).is_ready() {
+            log::trace!("Mutex::lock timed out");
+
+            let this = &mut *self;
+            this.lock_future
+                .mutex
+                .cancel_waiter(&mut this.lock_future.identifier);
+            Poll::Ready(None)
+        } else {
             Poll::Pending
         }
     }
 }
 
+impl<T> Future for OwnedLockFuture<T> {
+    type Output = OwnedLockGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<OwnedLockGuard<T>> {
+        let this = &mut *self;
+        let mutex = this.mutex.as_ref().expect("Polled after completion");
+
+        if mutex.try_acquire(&mut this.identifier) {
+            let mutex = this.mutex.take().unwrap();
+            mutex.clear_holder();
+            Poll::Ready(OwnedLockGuard { mutex })
+        } else {
+            mutex.register_waiter(&mut this.identifier, ctx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Drop for OwnedLockFuture<T> {
+    fn drop(&mut self) {
+        if let Some(mutex) = &self.mutex {
+            mutex.cancel_waiter(&mut self.identifier);
+        }
+    }
+}
+
 impl<'a, T> Future for CondWait<'a, T> {
     type Output = LockGuard<'a, T>;
 
     fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<LockGuard<'a, T>> {
         let mut lock_future = self.lock_future.borrow_mut();
 
-        if let Some(fut) = &mut *lock_future {
+        let result = if let Some(fut) = &mut *lock_future {
             LockFuture::poll(Pin::new(&mut *fut), ctx)
         } else if self.woken.load(Ordering::SeqCst) {
             let mut fut = self.mutex.lock();
@@ -171,7 +441,107 @@ impl<'a, T> Future for CondWait<'a, T> {
             waiters.push((self.woken.clone(), ctx.waker().clone()));
 
             Poll::Pending
+        };
+
+        if let (Poll::Ready(_), Some(recorder)) = (&result, &self.recorder) {
+            recorder.finish(false);
+        }
+
+        result
+    }
+}
+
+impl<'a, T> Future for CondTimeoutWait<'a, T> {
+    type Output = (LockGuard<'a, T>, WaitTimeoutResult);
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        if this.lock_future.is_none() {
+            if this.woken.load(Ordering::SeqCst) {
+                this.lock_future = Some(this.mutex.lock());
+            } else if Pin::new(&mut this.sleep_fut).poll(ctx).is_ready() {
+                log::trace!("Condvar::wait timed out");
+
+                // Make sure notify does not pick this waiter anymore
+                this.woken.store(true, Ordering::SeqCst);
+                remove_waiter(&this.waiters, &this.woken);
+                this.timed_out = true;
+                this.lock_future = Some(this.mutex.lock());
+            } else {
+                register_waiter(&this.waiters, &this.woken, ctx.waker());
+                return Poll::Pending;
+            }
+        }
+
+        let lock_future = this.lock_future.as_mut().unwrap();
+        let Poll::Ready(guard) = LockFuture::poll(Pin::new(lock_future), ctx) else {
+            return Poll::Pending;
+        };
+
+        if let Some(recorder) = &this.recorder {
+            recorder.finish(this.timed_out);
+        }
+
+        this.done = true;
+        Poll::Ready((guard, WaitTimeoutResult(this.timed_out)))
+    }
+}
+
+impl<T> Drop for CondTimeoutWait<'_, T> {
+    fn drop(&mut self) {
+        if self.done || self.timed_out {
+            return;
         }
+
+        remove_waiter(&self.waiters, &self.woken);
+
+        // We were notified but never returned, so pass the notification on
+        if self.woken.swap(true, Ordering::SeqCst) {
+            wake_one(&self.waiters);
+        }
+    }
+}
+
+/// Add a waiter to the list, or update its waker if it is in there already
+pub(super) fn register_waiter(
+    waiters: &RefCell<CondWaiters>,
+    woken: &Rc<AtomicBool>,
+    waker: &Waker,
+) {
+    let mut waiters = waiters.borrow_mut();
+
+    if let Some((_, entry)) = waiters.iter_mut().find(|(w, _)| Rc::ptr_eq(w, woken)) {
+        entry.clone_from(waker);
+    } else {
+        waiters.push((woken.clone(), waker.clone()));
+    }
+}
+
+pub(super) fn remove_waiter(waiters: &RefCell<CondWaiters>, woken: &Rc<AtomicBool>) {
+    waiters.borrow_mut().retain(|(w, _)| !Rc::ptr_eq(w, woken));
+}
+
+/// Wake the first waiter that has not been woken yet
+pub(super) fn wake_one(waiters: &RefCell<CondWaiters>) {
+    let mut waiters = waiters.borrow_mut();
+    let mut old_waiters = vec![];
+    std::mem::swap(&mut *waiters, &mut old_waiters);
+
+    let mut iter = old_waiters.into_iter();
+
+    // Find one waker that has not been woken yet
+    for (is_woken, waker) in iter.by_ref() {
+        if !is_woken.load(Ordering::SeqCst) {
+            is_woken.store(true, Ordering::SeqCst);
+            waker.wake();
+            break;
+        }
+    }
+
+    // Put the rest back on the waiter list
+    for e in iter {
+        waiters.push(e);
     }
 }
 
@@ -179,6 +549,28 @@ impl Condvar {
     pub fn new() -> Self {
         Self {
             waiters: Rc::new(RefCell::new(vec![])),
+            stats: None,
+        }
+    }
+
+    /// Create a condition variable that records how long tasks wait on it
+    pub fn with_stats() -> Self {
+        Self {
+            waiters: Rc::new(RefCell::new(vec![])),
+            stats: Some(Default::default()),
+        }
+    }
+
+    /// The statistics recorded so far
+    ///
+    /// Returns None if the condition variable was not created using `with_stats`
+    pub fn stats(&self) -> Option<CondvarStats> {
+        self.stats.as_ref().map(|stats| stats.borrow().clone())
+    }
+
+    fn record_notification(&self) {
+        if let Some(stats) = &self.stats {
+            stats.borrow_mut().notifications += 1;
         }
     }
 
@@ -190,32 +582,90 @@ impl Condvar {
             lock_future: RefCell::new(None),
             waiters: self.waiters.clone(),
             woken: Rc::new(AtomicBool::new(false)),
+            recorder: CondWaitRecorder::new(&self.stats),
         }
     }
 
-    pub fn notify_one(&self) {
-        let mut waiters = self.waiters.borrow_mut();
-        let mut old_waiters = vec![];
-        std::mem::swap(&mut *waiters, &mut old_waiters);
+    /// Like `wait` but gives up after the specified amount of time
+    ///
+    /// The lock is always re-acquired before returning, even on timeout
+    pub fn wait_timeout<'a, T>(
+        &self,
+        lock: LockGuard<'a, T>,
+        timeout: Duration,
+    ) -> CondTimeoutWait<'a, T> {
+        let mutex = lock.into_mutex();
 
-        let mut iter = old_waiters.into_iter();
+        CondTimeoutWait {
+            mutex,
+            lock_future: None,
+            sleep_fut: crate::time::sleep(timeout),
+            timed_out: false,
+            done: false,
+            waiters: self.waiters.clone(),
+            woken: Rc::new(AtomicBool::new(false)),
+            recorder: CondWaitRecorder::new(&self.stats),
+        }
+    }
 
-        // Find one waker that has not been woken yet
-        for (is_woken, waker) in iter.by_ref() {
-            if !is_woken.load(Ordering::SeqCst) {
-                is_woken.store(true, Ordering::SeqCst);
-                waker.wake();
-                break;
-            }
+    /// Wait until `condition` returns false
+    ///
+    /// The condition is checked before waiting, and again every time this task is notified
+    pub async fn wait_while<'a, T, F>(
+        &self,
+        mut lock: LockGuard<'a, T>,
+        mut condition: F,
+    ) -> LockGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *lock) {
+            lock = self.wait(lock).await;
         }
 
-        // Put the rest back on the waiter list
-        for e in iter {
-            waiters.push(e);
+        lock
+    }
+
+    /// Wait until `condition` returns false, but for at most the specified amount of time
+    ///
+    /// Only reports a timeout if the condition still holds after the time ran out
+    pub async fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut lock: LockGuard<'a, T>,
+        timeout: Duration,
+        mut condition: F,
+    ) -> (LockGuard<'a, T>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = crate::time::now() + timeout;
+
+        while condition(&mut *lock) {
+            let remaining = deadline.saturating_duration_since(crate::time::now());
+            if remaining.is_zero() {
+                return (lock, WaitTimeoutResult(true));
+            }
+
+            let (new_lock, result) = self.wait_timeout(lock, remaining).await;
+            lock = new_lock;
+
+            if result.timed_out() {
+                let timed_out = condition(&mut *lock);
+                return (lock, WaitTimeoutResult(timed_out));
+            }
         }
+
+        (lock, WaitTimeoutResult(false))
+    }
+
+    pub fn notify_one(&self) {
+        self.record_notification();
+        wake_one(&self.waiters);
     }
 
     pub fn notify_all(&self) {
+        self.record_notification();
+
         let mut waiters = self.waiters.borrow_mut();
 
         for (is_woken, waker) in waiters.drain(..) {
@@ -237,10 +687,13 @@ impl Default for Condvar {
 mod tests {
     use std::future::Future;
     use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
     use std::task::{Context, Poll};
 
     use super::{CondWait, Condvar, LockFuture, Mutex};
+    use crate::time::Duration;
 
     use futures::task::{waker_ref, ArcWake};
 
@@ -285,6 +738,246 @@ mod tests {
         }
     }
 
+    struct FlagWaker {
+        woken: AtomicBool,
+    }
+
+    impl ArcWake for FlagWaker {
+        fn wake_by_ref(self_ptr: &Arc<Self>) {
+            self_ptr.woken.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn cancel_woken_lock() {
+        let mutex = Mutex::new(());
+        let guard = mutex.try_lock().unwrap();
+
+        let waker1 = Arc::new(FlagWaker {
+            woken: AtomicBool::new(false),
+        });
+        let waker2 = Arc::new(FlagWaker {
+            woken: AtomicBool::new(false),
+        });
+
+        let mut lock_fut1 = mutex.lock();
+        let mut lock_fut2 = mutex.lock();
+
+        {
+            let waker = waker_ref(&waker1);
+            let context = &mut Context::from_waker(&waker);
+            let res = LockFuture::poll(Pin::new(&mut lock_fut1), context);
+            assert!(res.is_pending());
+        }
+
+        let waker = waker_ref(&waker2);
+        let context = &mut Context::from_waker(&waker);
+        let res = LockFuture::poll(Pin::new(&mut lock_fut2), context);
+        assert!(res.is_pending());
+
+        // Only the first waiter gets woken up
+        drop(guard);
+        assert!(waker1.woken.load(Ordering::SeqCst));
+        assert!(!waker2.woken.load(Ordering::SeqCst));
+
+        // If it gives up, the second waiter must not be forgotten
+        drop(lock_fut1);
+        assert!(waker2.woken.load(Ordering::SeqCst));
+
+        let res = LockFuture::poll(Pin::new(&mut lock_fut2), context);
+        assert!(res.is_ready());
+    }
+
+    #[test]
+    fn fifo_order() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(Mutex::new(vec![]));
+            let guard = mutex.lock().await;
+
+            for idx in 0..5 {
+                let mutex = mutex.clone();
+                crate::spawn(async move {
+                    crate::time::sleep(Duration::from_millis(idx)).await;
+                    mutex.lock().await.push(idx);
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(10)).await;
+            drop(guard);
+
+            // Barging is not possible while others are waiting
+            assert!(mutex.try_lock().is_none());
+
+            crate::time::sleep(Duration::from_millis(1)).await;
+            assert_eq!(*mutex.lock().await, vec![0, 1, 2, 3, 4]);
+        });
+    }
+
+    #[test]
+    fn lock_timeout_and_owned() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(Mutex::new(0));
+            let mut guard = mutex.clone().lock_owned().await;
+
+            crate::spawn(async move {
+                crate::time::sleep(Duration::from_millis(10)).await;
+                *guard += 1;
+            });
+
+            let result = mutex.lock_timeout(Duration::from_millis(5)).await;
+            assert!(result.is_none());
+
+            let result = mutex.lock_timeout(Duration::from_millis(10)).await;
+            assert_eq!(result.map(|guard| *guard), Some(1));
+            assert_eq!(crate::time::now().to_millis(), 10);
+        });
+    }
+
+    #[test]
+    fn condvar_timeout() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(Mutex::new(0));
+            let condvar = Rc::new(Condvar::new());
+
+            let guard = mutex.lock().await;
+            let (guard, result) = condvar.wait_timeout(guard, Duration::from_millis(5)).await;
+            assert!(result.timed_out());
+            drop(guard);
+
+            {
+                let mutex = mutex.clone();
+                let condvar = condvar.clone();
+                crate::spawn(async move {
+                    for _ in 0..3 {
+                        crate::time::sleep(Duration::from_millis(10)).await;
+                        *mutex.lock().await += 1;
+                        condvar.notify_all();
+                    }
+                });
+            }
+
+            let guard = mutex.lock().await;
+            let guard = condvar.wait_while(guard, |value| *value < 2).await;
+            assert_eq!(*guard, 2);
+            assert_eq!(crate::time::now().to_millis(), 25);
+
+            let (guard, result) = condvar
+                .wait_timeout_while(guard, Duration::from_millis(5), |value| *value < 3)
+                .await;
+            assert!(result.timed_out());
+            assert_eq!(*guard, 2);
+
+            let (guard, result) = condvar
+                .wait_timeout_while(guard, Duration::from_millis(10), |value| *value < 3)
+                .await;
+            assert!(!result.timed_out());
+            assert_eq!(*guard, 3);
+        });
+    }
+
+    #[test]
+    fn condvar_timeout_waiters() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Mutex::new(());
+            let condvar = Condvar::new();
+
+            let mut guard = mutex.lock().await;
+            for _ in 0..10 {
+                let mut wait = Box::pin(condvar.wait_timeout(guard, Duration::from_millis(1)));
+
+                // Polling again must not register the waiter twice
+                for _ in 0..3 {
+                    assert!(futures::poll!(&mut wait).is_pending());
+                    assert_eq!(condvar.waiters.borrow().len(), 1);
+                }
+
+                (guard, _) = wait.await;
+                assert!(condvar.waiters.borrow().is_empty());
+            }
+        });
+    }
+
+    #[test]
+    fn condvar_timeout_cancelled() {
+        for notify_before_drop in [false, true] {
+            crate::Runtime::new().block_on(async move {
+                let mutex = Rc::new(Mutex::new(()));
+                let condvar = Rc::new(Condvar::new());
+                let other_woken = Rc::new(AtomicBool::new(false));
+
+                let mut wait =
+                    Box::pin(condvar.wait_timeout(mutex.lock().await, Duration::from_millis(100)));
+                assert!(futures::poll!(&mut wait).is_pending());
+
+                {
+                    let (mutex, condvar) = (mutex.clone(), condvar.clone());
+                    let other_woken = other_woken.clone();
+                    crate::spawn(async move {
+                        let _guard = condvar.wait(mutex.lock().await).await;
+                        other_woken.store(true, Ordering::SeqCst);
+                    });
+                }
+                crate::time::sleep(Duration::from_millis(1)).await;
+
+                // The notification must reach the other task, no matter if the
+                // cancelled wait was notified before it was dropped
+                if notify_before_drop {
+                    condvar.notify_one();
+                    drop(wait);
+                } else {
+                    drop(wait);
+                    condvar.notify_one();
+                }
+
+                crate::time::sleep(Duration::from_millis(1)).await;
+                assert!(other_woken.load(Ordering::SeqCst));
+                assert!(condvar.waiters.borrow().is_empty());
+            });
+        }
+    }
+
+    #[test]
+    fn lock_stats() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(Mutex::with_stats(0));
+            let condvar = Rc::new(Condvar::with_stats());
+
+            for idx in 1..=2 {
+                let mutex = mutex.clone();
+                let condvar = condvar.clone();
+                crate::spawn(async move {
+                    crate::time::sleep(Duration::from_millis(10 * idx)).await;
+                    let mut guard = mutex.lock().await;
+                    *guard += 1;
+                    condvar.notify_all();
+
+                    // Keep holding the lock, so the notified task has to wait for it
+                    crate::time::sleep(Duration::from_millis(5)).await;
+                });
+            }
+
+            let guard = mutex.lock().await;
+            let guard = condvar.wait_while(guard, |value| *value < 2).await;
+            drop(guard);
+            assert_eq!(crate::time::now().to_millis(), 25);
+
+            let stats = mutex.stats().unwrap();
+            assert_eq!(stats.acquisitions, 5);
+            assert_eq!(stats.contended_acquisitions, 2);
+            assert_eq!(stats.total_wait(), Duration::from_millis(10));
+            assert_eq!(stats.max_wait(), Duration::from_millis(5));
+            assert_eq!(stats.hold_times.count(), 5);
+            assert_eq!(stats.hold_times.max(), Duration::from_millis(5));
+
+            let stats = condvar.stats().unwrap();
+            assert_eq!(stats.waits, 2);
+            assert_eq!(stats.timeouts, 0);
+            assert_eq!(stats.notifications, 2);
+            assert_eq!(stats.total_wait(), Duration::from_millis(25));
+            assert_eq!(stats.max_wait(), Duration::from_millis(15));
+        });
+    }
+
     #[test]
     fn condvar_notify() {
         let mutex = Mutex::new(());
@@ -0,0 +1,293 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::wait_queue::WaitQueue;
+use crate::deadlock::{self, Resource};
+
+struct RwLockInner {
+    num_readers: usize,
+    has_writer: bool,
+    waiters: WaitQueue,
+}
+
+/// A reader-writer lock with an async interface
+///
+/// Readers and writers are served in FIFO order,
+/// so a waiting writer is not starved by readers that arrive after it.
+pub struct RwLock<T> {
+    data: RefCell<T>,
+    inner: RefCell<RwLockInner>,
+}
+
+pub struct RwLockReadGuard<'a, T> {
+    data: Ref<'a, T>,
+    lock: &'a RwLock<T>,
+    /// The task holding the guard, for deadlock detection
+    holder: Option<u64>,
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    data: RefMut<'a, T>,
+    lock: &'a RwLock<T>,
+    /// The task holding the guard, for deadlock detection
+    holder: Option<u64>,
+}
+
+pub struct ReadFut<'a, T> {
+    lock: &'a RwLock<T>,
+    identifier: Option<u64>,
+}
+
+pub struct WriteFut<'a, T> {
+    lock: &'a RwLock<T>,
+    identifier: Option<u64>,
+}
+
+impl<T> RwLock<T> {
+    #[track_caller]
+    pub fn new(data: T) -> Self {
+        Self {
+            data: RefCell::new(data),
+            inner: RefCell::new(RwLockInner {
+                num_readers: 0,
+                has_writer: false,
+                waiters: WaitQueue::new(Resource::new("RwLock")),
+            }),
+        }
+    }
+
+    /// Acquire shared read access
+    pub fn read(&self) -> ReadFut<'_, T> {
+        ReadFut {
+            lock: self,
+            identifier: None,
+        }
+    }
+
+    /// Acquire exclusive write access
+    pub fn write(&self) -> WriteFut<'_, T> {
+        WriteFut {
+            lock: self,
+            identifier: None,
+        }
+    }
+
+    /// Acquire read access if that is possible without waiting
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.has_writer || !inner.waiters.is_empty() {
+            None
+        } else {
+            inner.num_readers += 1;
+            Some(RwLockReadGuard {
+                data: self.data.borrow(),
+                lock: self,
+                holder: deadlock::acquired(inner.waiters.resource()),
+            })
+        }
+    }
+
+    /// Acquire write access if that is possible without waiting
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.has_writer || inner.num_readers > 0 || !inner.waiters.is_empty() {
+            None
+        } else {
+            inner.has_writer = true;
+            Some(RwLockWriteGuard {
+                data: self.data.borrow_mut(),
+                lock: self,
+                holder: deadlock::acquired(inner.waiters.resource()),
+            })
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<'a, T> Future for ReadFut<'a, T> {
+    type Output = RwLockReadGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<RwLockReadGuard<'a, T>> {
+        let this = &mut *self;
+        let mut inner = this.lock.inner.borrow_mut();
+
+        if !inner.has_writer && inner.waiters.is_next(this.identifier) {
+            inner.num_readers += 1;
+
+            if let Some(identifier) = this.identifier.take() {
+                inner.waiters.remove(identifier);
+            }
+
+            // Let the next reader in as well
+            inner.waiters.wake_next();
+
+            Poll::Ready(RwLockReadGuard {
+                data: this.lock.data.borrow(),
+                lock: this.lock,
+                holder: deadlock::acquired(inner.waiters.resource()),
+            })
+        } else {
+            inner.waiters.register(&mut this.identifier, ctx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T> Future for WriteFut<'a, T> {
+    type Output = RwLockWriteGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<RwLockWriteGuard<'a, T>> {
+        let this = &mut *self;
+        let mut inner = this.lock.inner.borrow_mut();
+
+        if !inner.has_writer && inner.num_readers == 0 && inner.waiters.is_next(this.identifier) {
+            inner.has_writer = true;
+
+            if let Some(identifier) = this.identifier.take() {
+                inner.waiters.remove(identifier);
+            }
+
+            Poll::Ready(RwLockWriteGuard {
+                data: this.lock.data.borrow_mut(),
+                lock: this.lock,
+                holder: deadlock::acquired(inner.waiters.resource()),
+            })
+        } else {
+            inner.waiters.register(&mut this.identifier, ctx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+fn cancel_wait(inner: &RefCell<RwLockInner>, identifier: &mut Option<u64>) {
+    if let Some(identifier) = identifier.take() {
+        let mut inner = inner.borrow_mut();
+
+        // Pass on our turn, so the wakeup is not lost
+        if inner.waiters.remove(identifier) {
+            inner.waiters.wake_next();
+        }
+    }
+}
+
+impl<T> Drop for ReadFut<'_, T> {
+    fn drop(&mut self) {
+        cancel_wait(&self.lock.inner, &mut self.identifier);
+    }
+}
+
+impl<T> Drop for WriteFut<'_, T> {
+    fn drop(&mut self) {
+        cancel_wait(&self.lock.inner, &mut self.identifier);
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut inner = self.lock.inner.borrow_mut();
+        inner.num_readers -= 1;
+        deadlock::released(inner.waiters.resource(), self.holder);
+
+        if inner.num_readers == 0 {
+            inner.waiters.wake_next();
+        }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut inner = self.lock.inner.borrow_mut();
+        inner.has_writer = false;
+        deadlock::released(inner.waiters.resource(), self.holder);
+        inner.waiters.wake_next();
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::RwLock;
+    use crate::time::Duration;
+
+    #[test]
+    fn writer_not_starved() {
+        crate::Runtime::new().block_on(async {
+            let lock = Rc::new(RwLock::new(0));
+            let log = Rc::new(RefCell::new(vec![]));
+
+            let read_guard = lock.read().await;
+
+            // A writer followed by a reader, both have to wait for the first reader
+            {
+                let lock = lock.clone();
+                let log = log.clone();
+                crate::spawn(async move {
+                    let mut guard = lock.write().await;
+                    *guard += 1;
+                    log.borrow_mut()
+                        .push(("write", crate::time::now().to_millis()));
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(1)).await;
+
+            {
+                let lock = lock.clone();
+                let log = log.clone();
+                crate::spawn(async move {
+                    let guard = lock.read().await;
+                    assert_eq!(*guard, 1);
+                    log.borrow_mut()
+                        .push(("read", crate::time::now().to_millis()));
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(4)).await;
+            assert!(lock.try_read().is_none());
+            drop(read_guard);
+
+            crate::time::sleep(Duration::from_millis(20)).await;
+            assert_eq!(*log.borrow(), vec![("write", 5), ("read", 15)]);
+        });
+    }
+}
@@ -0,0 +1,291 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::stats::{LockStats, LockStatsRecorder};
+use super::wait_queue::WaitQueue;
+use crate::deadlock::{self, Resource};
+use crate::time::Time;
+
+struct SemaphoreInner {
+    permits: usize,
+    waiters: WaitQueue,
+    stats: Option<LockStatsRecorder>,
+}
+
+/// A counting semaphore
+///
+/// Permits are handed out in FIFO order. A task requesting many permits
+/// will block tasks behind it, even if they request fewer permits.
+pub struct Semaphore {
+    inner: RefCell<SemaphoreInner>,
+}
+
+/// Returned when not enough permits are available right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryAcquireError;
+
+/// Holds permits of a semaphore and returns them when dropped
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    /// Only tracked with stats enabled
+    acquired_at: Option<Time>,
+    /// The task holding the permits, for deadlock detection
+    holder: Option<u64>,
+}
+
+pub struct AcquireFut<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    identifier: Option<u64>,
+}
+
+impl Semaphore {
+    #[track_caller]
+    pub fn new(permits: usize) -> Self {
+        Self {
+            inner: RefCell::new(SemaphoreInner {
+                permits,
+                waiters: WaitQueue::new(Resource::new("Semaphore")),
+                stats: None,
+            }),
+        }
+    }
+
+    /// Create a semaphore that records contention statistics in simulated time
+    ///
+    /// Every acquisition counts once, no matter how many permits it takes
+    #[track_caller]
+    pub fn with_stats(permits: usize) -> Self {
+        let semaphore = Self::new(permits);
+        semaphore.inner.borrow_mut().stats = Some(LockStatsRecorder::default());
+        semaphore
+    }
+
+    /// The statistics recorded so far
+    ///
+    /// Returns None if the semaphore was not created using `with_stats`
+    pub fn stats(&self) -> Option<LockStats> {
+        self.inner
+            .borrow()
+            .stats
+            .as_ref()
+            .map(|stats| stats.get().clone())
+    }
+
+    /// Wait for a single permit
+    pub fn acquire(&self) -> AcquireFut<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Wait until `permits` permits are available and take all of them at once
+    pub fn acquire_many(&self, permits: usize) -> AcquireFut<'_> {
+        AcquireFut {
+            semaphore: self,
+            permits,
+            identifier: None,
+        }
+    }
+
+    /// Take a single permit if one is available without waiting
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Take `permits` permits if they are available without waiting
+    pub fn try_acquire_many(&self, permits: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut inner = self.inner.borrow_mut();
+
+        if inner.waiters.is_empty() && inner.permits >= permits {
+            inner.permits -= permits;
+            Ok(SemaphorePermit {
+                semaphore: self,
+                permits,
+                acquired_at: inner.stats.as_mut().map(|stats| stats.acquired(None)),
+                holder: deadlock::acquired(inner.waiters.resource()),
+            })
+        } else {
+            Err(TryAcquireError)
+        }
+    }
+
+    /// Add permits to the semaphore, e.g., to model a growing thread pool
+    pub fn add_permits(&self, permits: usize) {
+        let mut inner = self.inner.borrow_mut();
+        inner.permits += permits;
+        inner.waiters.wake_next();
+    }
+
+    /// The number of permits that are currently not held by anybody
+    pub fn available_permits(&self) -> usize {
+        self.inner.borrow().permits
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// The number of permits held
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Drop the permit without returning it to the semaphore
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    fn release(&mut self) {
+        let mut inner = self.semaphore.inner.borrow_mut();
+        deadlock::released(inner.waiters.resource(), self.holder.take());
+
+        if self.permits > 0 {
+            if let Some(since) = self.acquired_at {
+                inner.stats.as_mut().unwrap().released(since);
+            }
+        }
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.release();
+
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+impl<'a> Future for AcquireFut<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<SemaphorePermit<'a>> {
+        let this = &mut *self;
+        let mut inner = this.semaphore.inner.borrow_mut();
+
+        if inner.waiters.is_next(this.identifier) && inner.permits >= this.permits {
+            inner.permits -= this.permits;
+            let acquired_at = inner
+                .stats
+                .as_mut()
+                .map(|stats| stats.acquired(this.identifier));
+
+            if let Some(identifier) = this.identifier.take() {
+                inner.waiters.remove(identifier);
+            }
+
+            // There might be enough permits left for the next waiter
+            if inner.permits > 0 {
+                inner.waiters.wake_next();
+            }
+
+            Poll::Ready(SemaphorePermit {
+                semaphore: this.semaphore,
+                permits: this.permits,
+                acquired_at,
+                holder: deadlock::acquired(inner.waiters.resource()),
+            })
+        } else {
+            inner.waiters.register(&mut this.identifier, ctx.waker());
+
+            if let Some(stats) = &mut inner.stats {
+                stats.waiting(this.identifier.unwrap());
+            }
+
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for AcquireFut<'_> {
+    fn drop(&mut self) {
+        if let Some(identifier) = self.identifier.take() {
+            let mut inner = self.semaphore.inner.borrow_mut();
+
+            if let Some(stats) = &mut inner.stats {
+                stats.cancelled(identifier);
+            }
+
+            // Pass on our turn, so the wakeup is not lost
+            if inner.waiters.remove(identifier) {
+                inner.waiters.wake_next();
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for TryAcquireError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "not enough permits available")
+    }
+}
+
+impl std::error::Error for TryAcquireError {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::Semaphore;
+    use crate::time::Duration;
+
+    #[test]
+    fn fifo_weighted() {
+        crate::Runtime::new().block_on(async {
+            let semaphore = Rc::new(Semaphore::new(3));
+            let order = Rc::new(RefCell::new(vec![]));
+
+            let permit = semaphore.acquire_many(2).await;
+            assert_eq!(semaphore.available_permits(), 1);
+
+            // The large request is first, so the small one has to wait behind it
+            for (idx, permits) in [(0, 3), (1, 1)] {
+                let semaphore = semaphore.clone();
+                let order = order.clone();
+
+                crate::spawn(async move {
+                    let _permit = semaphore.acquire_many(permits).await;
+                    order
+                        .borrow_mut()
+                        .push((idx, crate::time::now().to_millis()));
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(5)).await;
+            assert!(semaphore.try_acquire().is_err());
+            drop(permit);
+
+            crate::time::sleep(Duration::from_millis(30)).await;
+            assert_eq!(*order.borrow(), vec![(0, 5), (1, 15)]);
+            assert_eq!(semaphore.available_permits(), 3);
+        });
+    }
+
+    #[test]
+    fn stats() {
+        crate::Runtime::new().block_on(async {
+            let semaphore = Rc::new(Semaphore::with_stats(2));
+
+            for _ in 0..3 {
+                let semaphore = semaphore.clone();
+                crate::spawn(async move {
+                    let _permit = semaphore.acquire().await;
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                });
+            }
+
+            crate::time::sleep(Duration::from_millis(30)).await;
+
+            let stats = semaphore.stats().unwrap();
+            assert_eq!(stats.acquisitions, 3);
+            assert_eq!(stats.contended_acquisitions, 1);
+            assert_eq!(stats.max_wait(), Duration::from_millis(10));
+            assert_eq!(stats.hold_times.mean(), Duration::from_millis(10));
+            assert!(Semaphore::new(1).stats().is_none());
+        });
+    }
+}
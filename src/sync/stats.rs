@@ -0,0 +1,257 @@
+/// Contention statistics for the synchronization primitives
+///
+/// Statistics are only recorded for primitives created with `with_stats`,
+/// so that regular locks do not need to query the timer.
+use std::collections::HashMap;
+
+use crate::time::{Duration, Time};
+
+const NUM_BUCKETS: usize = 65;
+
+/// A distribution of durations
+///
+/// Samples are counted in buckets whose bounds are powers of two (in nanoseconds),
+/// so percentiles are approximate, while count, total, min, and max are exact.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// Bucket 0 holds zero durations, bucket i holds durations in [2^(i-1), 2^i) ns
+    buckets: [u64; NUM_BUCKETS],
+    count: u64,
+    total: u128,
+    min: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = duration.as_nanos();
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += nanos as u128;
+
+        if self.count == 1 || duration < self.min {
+            self.min = duration;
+        }
+        self.max = self.max.max(duration);
+    }
+
+    /// The number of samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of all samples (saturates at `Duration::MAX`)
+    pub fn total(&self) -> Duration {
+        Duration::from_nanos(self.total.try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The average sample, or zero if there are none
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.total / self.count as u128) as u64)
+        }
+    }
+
+    /// An upper bound for the specified percentile (between 0 and 100)
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        assert!(
+            (0.0..=100.0).contains(&percentile),
+            "Percentile must be between 0 and 100"
+        );
+
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+
+        for (upper, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return upper.min(self.max);
+            }
+        }
+
+        self.max
+    }
+
+    /// All non-empty buckets as (inclusive upper bound, number of samples)
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(idx, count)| {
+                let upper = if idx == 0 {
+                    0
+                } else {
+                    u64::MAX >> (u64::BITS as usize - idx)
+                };
+                (Duration::from_nanos(upper), *count)
+            })
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; NUM_BUCKETS],
+            count: 0,
+            total: 0,
+            min: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+/// Statistics of a `Mutex` or `Semaphore`
+#[derive(Debug, Clone, Default)]
+pub struct LockStats {
+    /// How often the lock (or a permit) was acquired
+    pub acquisitions: u64,
+    /// How many of these acquisitions had to wait
+    pub contended_acquisitions: u64,
+    /// How long contended acquisitions waited
+    pub wait_times: Histogram,
+    /// How long the lock (or a permit) was held before being released
+    pub hold_times: Histogram,
+}
+
+impl LockStats {
+    /// Total time spent waiting for the lock
+    pub fn total_wait(&self) -> Duration {
+        self.wait_times.total()
+    }
+
+    /// Longest time spent waiting for the lock
+    pub fn max_wait(&self) -> Duration {
+        self.wait_times.max()
+    }
+
+    /// The fraction of acquisitions that had to wait
+    pub fn contention_ratio(&self) -> f64 {
+        if self.acquisitions == 0 {
+            0.0
+        } else {
+            self.contended_acquisitions as f64 / self.acquisitions as f64
+        }
+    }
+}
+
+/// Statistics of a `Condvar`
+#[derive(Debug, Clone, Default)]
+pub struct CondvarStats {
+    /// How many waits completed
+    pub waits: u64,
+    /// How many of these waits timed out
+    pub timeouts: u64,
+    /// How many calls to `notify_one` and `notify_all` there were
+    pub notifications: u64,
+    /// How long waits took, including re-acquiring the lock
+    pub wait_times: Histogram,
+}
+
+impl CondvarStats {
+    pub fn total_wait(&self) -> Duration {
+        self.wait_times.total()
+    }
+
+    pub fn max_wait(&self) -> Duration {
+        self.wait_times.max()
+    }
+}
+
+/// Keeps track of when waiters were enqueued
+#[derive(Default)]
+pub(super) struct LockStatsRecorder {
+    stats: LockStats,
+    wait_start: HashMap<u64, Time>,
+}
+
+impl LockStatsRecorder {
+    pub fn get(&self) -> &LockStats {
+        &self.stats
+    }
+
+    /// A waiter was added to the queue
+    pub fn waiting(&mut self, identifier: u64) {
+        self.wait_start
+            .entry(identifier)
+            .or_insert_with(crate::time::now);
+    }
+
+    /// A waiter gave up
+    pub fn cancelled(&mut self, identifier: u64) {
+        self.wait_start.remove(&identifier);
+    }
+
+    /// The lock was acquired, either directly or by a waiter
+    ///
+    /// Returns the time of acquisition
+    pub fn acquired(&mut self, identifier: Option<u64>) -> Time {
+        let now = crate::time::now();
+        self.stats.acquisitions += 1;
+
+        if let Some(start) = identifier.and_then(|id| self.wait_start.remove(&id)) {
+            self.stats.contended_acquisitions += 1;
+            self.stats
+                .wait_times
+                .record(now.saturating_duration_since(start));
+        }
+
+        now
+    }
+
+    /// The lock that was acquired at `since` has been released
+    pub fn released(&mut self, since: Time) {
+        let now = crate::time::now();
+        self.stats
+            .hold_times
+            .record(now.saturating_duration_since(since));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+    use crate::time::Duration;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(50.0), Duration::ZERO);
+
+        for nanos in [0, 1, 5, 6, 7, 100] {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+
+        assert_eq!(histogram.count(), 6);
+        assert_eq!(histogram.total(), Duration::from_nanos(119));
+        assert_eq!(histogram.min(), Duration::ZERO);
+        assert_eq!(histogram.max(), Duration::from_nanos(100));
+        assert_eq!(histogram.mean(), Duration::from_nanos(19));
+
+        assert_eq!(histogram.percentile(0.0), Duration::ZERO);
+        assert_eq!(histogram.percentile(50.0), Duration::from_nanos(7));
+        assert_eq!(histogram.percentile(100.0), Duration::from_nanos(100));
+
+        let buckets: Vec<_> = histogram
+            .buckets()
+            .map(|(upper, count)| (upper.as_nanos(), count))
+            .collect();
+        assert_eq!(buckets, vec![(0, 1), (1, 1), (7, 3), (127, 1)]);
+    }
+}
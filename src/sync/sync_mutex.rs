@@ -2,12 +2,20 @@
 use std::cell::{RefCell, RefMut};
 use std::future::Future;
 use std::ops::{Deref, DerefMut};
+#[cfg(debug_assertions)]
+use std::panic::Location;
 use std::pin::Pin;
 use std::rc::Rc;
+#[cfg(debug_assertions)]
+use std::rc::Weak;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::task::{Context, Poll, Waker};
 
+use super::mutex::{register_waiter, remove_waiter, wake_one};
+use super::WaitTimeoutResult;
 use crate::time::{Duration, SleepFut};
+#[cfg(debug_assertions)]
+use crate::Task;
 
 type CondWaiters = Vec<(Rc<AtomicBool>, Waker)>;
 
@@ -22,22 +30,37 @@ pub struct SyncCondWait<'a, T> {
     waiters: Rc<RefCell<CondWaiters>>,
 }
 
-pub struct SyncCondTimeoutWait<'a, T> {
+/// Returned by [`SyncCondvar::wait_timeout`], resolves to the guard and whether the wait timed out
+pub struct SyncCondWaitTimeoutResult<'a, T> {
     mutex: &'a SyncMutex<T>,
+    /// Set once the future returned the lock
+    done: bool,
     woken: Rc<AtomicBool>,
     sleep_fut: SleepFut,
     waiters: Rc<RefCell<CondWaiters>>,
 }
 
+/// Returned by [`SyncCondvar::wait_with_timeout`], resolves to the guard only
+pub struct SyncCondTimeoutWait<'a, T> {
+    inner: SyncCondWaitTimeoutResult<'a, T>,
+}
+
 /// A mutex that is just a wrapper around RefCell
 /// Do not hold this lock while calling await
+///
+/// In debug builds, the runtime panics if a task is suspended while holding the lock
 pub struct SyncMutex<T> {
     inner: RefCell<T>,
+    #[cfg(debug_assertions)]
+    created_at: &'static Location<'static>,
 }
 
 pub struct SyncLockGuard<'a, T> {
     data: RefMut<'a, T>,
     mutex: &'a SyncMutex<T>,
+    /// The task that acquired the lock
+    #[cfg(debug_assertions)]
+    task: Option<Weak<Task>>,
 }
 
 impl<'a, T> SyncLockGuard<'a, T> {
@@ -46,20 +69,45 @@ impl<'a, T> SyncLockGuard<'a, T> {
     }
 }
 
+impl<T> Drop for SyncLockGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        if let Some(task) = self.task.take().and_then(|task| task.upgrade()) {
+            task.remove_sync_lock(self.mutex.created_at);
+        }
+    }
+}
+
 impl<T> SyncMutex<T> {
+    #[track_caller]
     pub fn new(data: T) -> Self {
         Self {
             inner: RefCell::new(data),
+            #[cfg(debug_assertions)]
+            created_at: Location::caller(),
         }
     }
 
     pub fn lock(&self) -> SyncLockGuard<'_, T> {
         let data = self.inner.borrow_mut();
-        SyncLockGuard { data, mutex: self }
+
+        #[cfg(debug_assertions)]
+        let task = crate::current_task().map(|task| {
+            task.add_sync_lock(self.created_at);
+            Rc::downgrade(&task)
+        });
+
+        SyncLockGuard {
+            data,
+            mutex: self,
+            #[cfg(debug_assertions)]
+            task,
+        }
     }
 }
 
 impl<T: Default> Default for SyncMutex<T> {
+    #[track_caller]
     fn default() -> Self {
         Self::new(T::default())
     }
@@ -93,23 +141,51 @@ impl<'a, T> Future for SyncCondWait<'a, T> {
     }
 }
 
-impl<'a, T> Future for SyncCondTimeoutWait<'a, T> {
-    type Output = SyncLockGuard<'a, T>;
+impl<'a, T> Future for SyncCondWaitTimeoutResult<'a, T> {
+    type Output = (SyncLockGuard<'a, T>, WaitTimeoutResult);
 
-    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<SyncLockGuard<'a, T>> {
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
         if self.woken.load(Ordering::SeqCst) {
-            Poll::Ready(self.mutex.lock())
+            self.done = true;
+            Poll::Ready((self.mutex.lock(), WaitTimeoutResult(false)))
         } else if SleepFut::poll(Pin::new(&mut self.sleep_fut), ctx) == Poll::Ready(()) {
             log::trace!("Condvar::wait timed out");
-            Poll::Ready(self.mutex.lock())
+
+            // Make sure notify does not pick this waiter anymore
+            self.woken.store(true, Ordering::SeqCst);
+            remove_waiter(&self.waiters, &self.woken);
+            self.done = true;
+            Poll::Ready((self.mutex.lock(), WaitTimeoutResult(true)))
         } else {
-            let mut waiters = self.waiters.borrow_mut();
-            waiters.push((self.woken.clone(), ctx.waker().clone()));
+            register_waiter(&self.waiters, &self.woken, ctx.waker());
             Poll::Pending
         }
     }
 }
 
+impl<T> Drop for SyncCondWaitTimeoutResult<'_, T> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        remove_waiter(&self.waiters, &self.woken);
+
+        // We were notified but never returned, so pass the notification on
+        if self.woken.swap(true, Ordering::SeqCst) {
+            wake_one(&self.waiters);
+        }
+    }
+}
+
+impl<'a, T> Future for SyncCondTimeoutWait<'a, T> {
+    type Output = SyncLockGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<SyncLockGuard<'a, T>> {
+        Pin::new(&mut self.inner).poll(ctx).map(|(guard, _)| guard)
+    }
+}
+
 impl SyncCondvar {
     pub fn new() -> Self {
         Self::default()
@@ -125,42 +201,87 @@ impl SyncCondvar {
         }
     }
 
-    pub fn wait_with_timeout<'a, T>(
+    /// Like `wait` but gives up after the specified amount of time
+    pub fn wait_timeout<'a, T>(
         &self,
         lock: SyncLockGuard<'a, T>,
         timeout: Duration,
-    ) -> SyncCondTimeoutWait<'a, T> {
+    ) -> SyncCondWaitTimeoutResult<'a, T> {
         assert!(!timeout.is_zero());
         let mutex = lock.into_mutex();
 
-        SyncCondTimeoutWait {
+        SyncCondWaitTimeoutResult {
             mutex,
+            done: false,
             sleep_fut: crate::time::sleep(timeout),
             waiters: self.waiters.clone(),
             woken: Rc::new(AtomicBool::new(false)),
         }
     }
 
-    pub fn notify_one(&self) {
-        let mut waiters = self.waiters.borrow_mut();
-        let mut old_waiters = vec![];
-        std::mem::swap(&mut *waiters, &mut old_waiters);
+    /// Like `wait_timeout` but does not tell whether the wait timed out
+    pub fn wait_with_timeout<'a, T>(
+        &self,
+        lock: SyncLockGuard<'a, T>,
+        timeout: Duration,
+    ) -> SyncCondTimeoutWait<'a, T> {
+        SyncCondTimeoutWait {
+            inner: self.wait_timeout(lock, timeout),
+        }
+    }
 
-        let mut iter = old_waiters.into_iter();
+    /// Wait until `condition` returns false
+    ///
+    /// The condition is checked before waiting, and again every time this task is notified
+    pub async fn wait_while<'a, T, F>(
+        &self,
+        mut lock: SyncLockGuard<'a, T>,
+        mut condition: F,
+    ) -> SyncLockGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *lock) {
+            lock = self.wait(lock).await;
+        }
 
-        // Find one waker that has not been woken yet
-        for (is_woken, waker) in iter.by_ref() {
-            if !is_woken.load(Ordering::SeqCst) {
-                is_woken.store(true, Ordering::SeqCst);
-                waker.wake();
-                break;
+        lock
+    }
+
+    /// Wait until `condition` returns false, but for at most the specified amount of time
+    ///
+    /// Only reports a timeout if the condition still holds after the time ran out
+    pub async fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut lock: SyncLockGuard<'a, T>,
+        timeout: Duration,
+        mut condition: F,
+    ) -> (SyncLockGuard<'a, T>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = crate::time::now() + timeout;
+
+        while condition(&mut *lock) {
+            let remaining = deadline.saturating_duration_since(crate::time::now());
+            if remaining.is_zero() {
+                return (lock, WaitTimeoutResult(true));
             }
-        }
 
-        // Put the rest back on the waiter list
-        for e in iter {
-            waiters.push(e);
+            let (new_lock, result) = self.wait_timeout(lock, remaining).await;
+            lock = new_lock;
+
+            if result.timed_out() {
+                let timed_out = condition(&mut *lock);
+                return (lock, WaitTimeoutResult(timed_out));
+            }
         }
+
+        (lock, WaitTimeoutResult(false))
+    }
+
+    pub fn notify_one(&self) {
+        wake_one(&self.waiters);
     }
 
     pub fn notify_all(&self) {
@@ -174,3 +295,96 @@ impl SyncCondvar {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{SyncCondvar, SyncMutex};
+    use crate::time::Duration;
+
+    #[test]
+    fn wait_with_timeout() {
+        crate::Runtime::new().block_on(async {
+            let mutex = SyncMutex::new(1);
+            let condvar = SyncCondvar::new();
+
+            let guard = condvar
+                .wait_with_timeout(mutex.lock(), Duration::from_millis(5))
+                .await;
+            assert_eq!(*guard, 1);
+
+            let (_guard, result) = condvar.wait_timeout(guard, Duration::from_millis(5)).await;
+            assert!(result.timed_out());
+            assert_eq!(crate::time::now().to_millis(), 10);
+        });
+    }
+
+    #[test]
+    fn wait_timeout_waiters() {
+        crate::Runtime::new().block_on(async {
+            let mutex = SyncMutex::new(());
+            let condvar = SyncCondvar::new();
+
+            let mut guard = mutex.lock();
+            for _ in 0..10 {
+                let mut wait = Box::pin(condvar.wait_timeout(guard, Duration::from_millis(1)));
+
+                for _ in 0..3 {
+                    assert!(futures::poll!(&mut wait).is_pending());
+                    assert_eq!(condvar.waiters.borrow().len(), 1);
+                }
+
+                (guard, _) = wait.await;
+                assert!(condvar.waiters.borrow().is_empty());
+            }
+        });
+    }
+
+    #[test]
+    fn wait_timeout_while() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(SyncMutex::new(false));
+            let condvar = Rc::new(SyncCondvar::new());
+
+            {
+                let mutex = mutex.clone();
+                let condvar = condvar.clone();
+                crate::spawn(async move {
+                    crate::time::sleep(Duration::from_millis(10)).await;
+                    *mutex.lock() = true;
+                    condvar.notify_one();
+                });
+            }
+
+            let (guard, result) = condvar
+                .wait_timeout_while(mutex.lock(), Duration::from_millis(5), |ready| !*ready)
+                .await;
+            assert!(result.timed_out());
+            assert!(!*guard);
+
+            let guard = condvar.wait_while(guard, |ready| !*ready).await;
+            assert!(*guard);
+            assert_eq!(crate::time::now().to_millis(), 10);
+        });
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(
+        expected = "Task \"holder\" awaited while holding a lock of the SyncMutex created at src/sync/sync_mutex.rs"
+    )]
+    fn held_across_await() {
+        crate::Runtime::new().block_on(async {
+            let mutex = Rc::new(SyncMutex::new(0));
+
+            crate::spawn_named("holder", async move {
+                let mut guard = mutex.lock();
+                crate::time::sleep(Duration::from_millis(1)).await;
+                *guard += 1;
+            });
+
+            crate::time::sleep(Duration::from_millis(5)).await;
+        });
+    }
+}
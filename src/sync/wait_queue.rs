@@ -0,0 +1,98 @@
+/// FIFO bookkeeping of tasks waiting for a synchronization primitive
+use std::collections::VecDeque;
+use std::task::Waker;
+
+use crate::deadlock::{Resource, Waiting};
+
+pub(crate) struct WaitQueue {
+    waiters: VecDeque<(u64, Waker, Waiting)>,
+    next_waiter_id: u64,
+    /// The primitive this queue belongs to, for deadlock detection
+    resource: Resource,
+}
+
+impl WaitQueue {
+    /// Create a queue of tasks that wait for the specified resource
+    pub fn new(resource: Resource) -> Self {
+        Self {
+            waiters: VecDeque::new(),
+            next_waiter_id: 0,
+            resource,
+        }
+    }
+
+    pub fn resource(&self) -> &Resource {
+        &self.resource
+    }
+
+    /// Add a waiter to the end of the queue, or update its waker if it is already queued
+    ///
+    /// `identifier` is set the first time the waiter gets enqueued
+    pub fn register(&mut self, identifier: &mut Option<u64>, waker: &Waker) {
+        if let Some(identifier) = identifier {
+            let entry = self
+                .waiters
+                .iter_mut()
+                .find(|(id, _, _)| id == identifier)
+                .expect("Waiter is not in the queue");
+
+            if !entry.1.will_wake(waker) {
+                entry.1 = waker.clone();
+            }
+        } else {
+            let id = self.next_waiter_id;
+            self.next_waiter_id += 1;
+
+            let waiting = Waiting::new(&self.resource);
+            self.waiters.push_back((id, waker.clone(), waiting));
+            *identifier = Some(id);
+        }
+    }
+
+    /// Is it this waiter's turn?
+    ///
+    /// This is also true for a waiter that has not been enqueued if the queue is empty
+    pub fn is_next(&self, identifier: Option<u64>) -> bool {
+        match (self.waiters.front(), identifier) {
+            (None, _) => true,
+            (Some((first, _, _)), Some(identifier)) => *first == identifier,
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Remove a waiter from the queue
+    ///
+    /// Returns true if it was at the front of the queue
+    pub fn remove(&mut self, identifier: u64) -> bool {
+        let Some(pos) = self.waiters.iter().position(|(id, _, _)| *id == identifier) else {
+            return false;
+        };
+
+        self.waiters.remove(pos);
+        pos == 0
+    }
+
+    /// Wake up the waiter at the front of the queue (if any)
+    ///
+    /// The waiter stays in the queue until it removes itself
+    pub fn wake_next(&self) {
+        if let Some((_, waker, _)) = self.waiters.front() {
+            waker.wake_by_ref();
+        }
+    }
+
+    /// Wake up all waiters
+    pub fn wake_all(&self) {
+        for (_, waker, _) in self.waiters.iter() {
+            waker.wake_by_ref();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+}
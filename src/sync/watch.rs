@@ -0,0 +1,313 @@
+/// Channels that only keep the latest value and notify receivers about changes
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use crate::deadlock::{EndpointUser, Resource, Waiting};
+
+struct Inner<T> {
+    value: RefCell<T>,
+    state: RefCell<State>,
+    /// Represents all senders for deadlock detection
+    senders: Resource,
+}
+
+struct State {
+    /// Incremented every time the value changes
+    version: u64,
+    num_senders: usize,
+    num_receivers: usize,
+    next_receiver_id: u64,
+    waiters: HashMap<u64, Waker>,
+}
+
+/// Returned when sending to a channel without receivers
+///
+/// Contains the value that could not be sent
+pub struct SendError<T>(pub T);
+
+/// Returned when all senders are gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+pub struct Sender<T> {
+    inner: Rc<Inner<T>>,
+    user: EndpointUser,
+}
+
+impl<T> Sender<T> {
+    /// Replace the current value and notify all receivers
+    ///
+    /// Fails if there are no receivers
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.inner.state.borrow().num_receivers == 0 {
+            return Err(SendError(value));
+        }
+
+        self.send_replace(value);
+        Ok(())
+    }
+
+    /// Replace the current value, even if there are no receivers, and return the old one
+    pub fn send_replace(&self, value: T) -> T {
+        self.user.touch();
+        let old = self.inner.value.replace(value);
+        self.notify();
+        old
+    }
+
+    /// Modify the current value in place and notify all receivers
+    pub fn send_modify(&self, func: impl FnOnce(&mut T)) {
+        self.user.touch();
+        func(&mut self.inner.value.borrow_mut());
+        self.notify();
+    }
+
+    /// Get a reference to the current value
+    ///
+    /// Do not hold this while calling await
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.inner.value.borrow()
+    }
+
+    /// Create a new receiver that has seen the current value
+    pub fn subscribe(&self) -> Receiver<T> {
+        let version = self.inner.state.borrow().version;
+        Receiver::new(self.inner.clone(), version)
+    }
+
+    /// The number of receivers that currently exist
+    pub fn receiver_count(&self) -> usize {
+        self.inner.state.borrow().num_receivers
+    }
+
+    fn notify(&self) {
+        let mut state = self.inner.state.borrow_mut();
+        state.version += 1;
+
+        log::trace!("Watch channel updated to version {}", state.version);
+
+        for (_, waker) in state.waiters.drain() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.inner.state.borrow_mut().num_senders += 1;
+
+        Self {
+            inner: self.inner.clone(),
+            user: EndpointUser::new(&self.inner.senders),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.borrow_mut();
+        state.num_senders -= 1;
+
+        if state.num_senders == 0 {
+            for (_, waker) in state.waiters.drain() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    inner: Rc<Inner<T>>,
+    identifier: u64,
+    /// The last version this receiver has seen
+    seen_version: u64,
+}
+
+impl<T> Receiver<T> {
+    fn new(inner: Rc<Inner<T>>, seen_version: u64) -> Self {
+        let identifier = {
+            let mut state = inner.state.borrow_mut();
+            state.num_receivers += 1;
+
+            let identifier = state.next_receiver_id;
+            state.next_receiver_id += 1;
+            identifier
+        };
+
+        Self {
+            inner,
+            identifier,
+            seen_version,
+        }
+    }
+
+    /// Get a reference to the current value without marking it as seen
+    ///
+    /// Do not hold this while calling await
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.inner.value.borrow()
+    }
+
+    /// Get a reference to the current value and mark it as seen
+    ///
+    /// Do not hold this while calling await
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        self.seen_version = self.inner.state.borrow().version;
+        self.inner.value.borrow()
+    }
+
+    /// Has the value changed since it was last seen?
+    ///
+    /// Fails if all senders are gone
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = self.inner.state.borrow();
+
+        if state.num_senders == 0 {
+            Err(RecvError)
+        } else {
+            Ok(state.version != self.seen_version)
+        }
+    }
+
+    /// Wait until the value changes and mark the new value as seen
+    ///
+    /// Returns immediately if there is a value that has not been seen yet.
+    /// Fails if all senders are gone.
+    #[must_use]
+    pub fn changed(&mut self) -> ChangedFut<'_, T> {
+        ChangedFut {
+            receiver: self,
+            waiting: None,
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    /// The new receiver has seen the same values as this one
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone(), self.seen_version)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.borrow_mut();
+        state.num_receivers -= 1;
+        state.waiters.remove(&self.identifier);
+    }
+}
+
+pub struct ChangedFut<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    waiting: Option<Waiting>,
+}
+
+impl<T> Future for ChangedFut<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let receiver = &mut *this.receiver;
+        let mut state = receiver.inner.state.borrow_mut();
+
+        if state.version != receiver.seen_version {
+            receiver.seen_version = state.version;
+            this.waiting = None;
+            Poll::Ready(Ok(()))
+        } else if state.num_senders == 0 {
+            this.waiting = None;
+            Poll::Ready(Err(RecvError))
+        } else {
+            state
+                .waiters
+                .insert(receiver.identifier, ctx.waker().clone());
+
+            if this.waiting.is_none() {
+                this.waiting = Some(Waiting::new(&receiver.inner.senders));
+            }
+
+            Poll::Pending
+        }
+    }
+}
+
+/// Create a watch channel with the given initial value
+#[track_caller]
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let senders = Resource::new("watch::Sender");
+    let inner = Rc::new(Inner {
+        value: RefCell::new(init),
+        state: RefCell::new(State {
+            version: 0,
+            num_senders: 1,
+            num_receivers: 0,
+            next_receiver_id: 0,
+            waiters: HashMap::new(),
+        }),
+        senders,
+    });
+
+    let receiver = Receiver::new(inner.clone(), 0);
+    let user = EndpointUser::new(&senders);
+    (Sender { inner, user }, receiver)
+}
+
+impl<T> std::fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> std::fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "channel has no receivers")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+impl std::fmt::Display for RecvError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "channel closed")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, RecvError};
+    use crate::time::Duration;
+
+    #[test]
+    fn latest_value() {
+        crate::Runtime::new().block_on(async {
+            let (sender, mut receiver) = channel(0);
+
+            crate::spawn(async move {
+                crate::time::sleep(Duration::from_millis(5)).await;
+                sender.send(1).unwrap();
+                sender.send(2).unwrap();
+                crate::time::sleep(Duration::from_millis(5)).await;
+                sender.send_modify(|value| *value += 1);
+            });
+
+            assert_eq!(*receiver.borrow(), 0);
+            assert!(!receiver.has_changed().unwrap());
+
+            // Intermediate values are skipped
+            receiver.changed().await.unwrap();
+            assert_eq!(*receiver.borrow(), 2);
+            assert_eq!(crate::time::now().to_millis(), 5);
+
+            receiver.changed().await.unwrap();
+            assert_eq!(*receiver.borrow_and_update(), 3);
+
+            assert_eq!(receiver.changed().await, Err(RecvError));
+        });
+    }
+}
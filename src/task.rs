@@ -1,5 +1,7 @@
 use std::cell::RefCell;
 use std::future::Future;
+#[cfg(debug_assertions)]
+use std::panic::Location;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -32,21 +34,83 @@ impl ArcWake for RcWrapper {
     }
 }
 
+/// The priority class of a task
+///
+/// When multiple tasks are ready at the same simulated time,
+/// tasks with a higher priority are run first.
+/// Tasks of the same priority run in the order they became ready.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
 pub struct Task {
+    identifier: u64,
+    name: Option<String>,
     future: Mutex<Option<BoxFuture<'static, ()>>>,
     ready_tasks: Rc<RefCell<TaskQueue>>,
+    priority: Priority,
+    /// Where the SyncMutexes this task currently holds a lock of were created
+    #[cfg(debug_assertions)]
+    held_sync_locks: RefCell<Vec<&'static Location<'static>>>,
 }
 
 impl Task {
     pub(crate) fn new(
+        identifier: u64,
+        name: Option<String>,
         future: impl Future<Output = ()> + 'static,
+        priority: Priority,
         ready_tasks: Rc<RefCell<TaskQueue>>,
     ) -> Self {
         let future = Box::pin(future);
 
         Self {
+            identifier,
+            name,
             future: Mutex::new(Some(future)),
             ready_tasks,
+            priority,
+            #[cfg(debug_assertions)]
+            held_sync_locks: Default::default(),
+        }
+    }
+
+    /// A number that uniquely identifies this task within its runtime
+    pub fn get_identifier(&self) -> u64 {
+        self.identifier
+    }
+
+    /// The name given to the task when it was spawned (if any)
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_priority(&self) -> Priority {
+        self.priority
+    }
+
+    #[cfg(debug_assertions)]
+    pub(crate) fn add_sync_lock(&self, location: &'static Location<'static>) {
+        self.held_sync_locks.borrow_mut().push(location);
+    }
+
+    #[cfg(debug_assertions)]
+    pub(crate) fn remove_sync_lock(&self, location: &'static Location<'static>) {
+        let mut locks = self.held_sync_locks.borrow_mut();
+        if let Some(pos) = locks.iter().position(|l| std::ptr::eq(*l, location)) {
+            locks.swap_remove(pos);
+        }
+    }
+
+    /// Panics if the task holds a SyncLockGuard while it is suspended
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_no_sync_locks(&self) {
+        if let Some(location) = self.held_sync_locks.borrow().first() {
+            panic!("{self} awaited while holding a lock of the SyncMutex created at {location}");
         }
     }
 
@@ -54,3 +118,13 @@ impl Task {
         self.future.lock()
     }
 }
+
+impl std::fmt::Display for Task {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            write!(fmt, "Task \"{name}\"")
+        } else {
+            write!(fmt, "Task #{}", self.identifier)
+        }
+    }
+}
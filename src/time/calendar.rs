@@ -0,0 +1,86 @@
+/// Conversion of wall-clock time into calendar dates
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Format a point in (real) time as an RFC 3339 date in UTC
+///
+/// For example, "2023-11-14T22:13:20.5Z"
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let (seconds, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+        Err(err) => {
+            // Before 1970, so round the seconds down and make the fraction positive
+            let before = err.duration();
+            let mut seconds = -(before.as_secs() as i64);
+            let mut nanos = before.subsec_nanos();
+            if nanos > 0 {
+                seconds -= 1;
+                nanos = 1_000_000_000 - nanos;
+            }
+            (seconds, nanos)
+        }
+    };
+
+    let days = seconds.div_euclid(24 * 60 * 60);
+    let secs_of_day = seconds.rem_euclid(24 * 60 * 60);
+    let (year, month, day) = civil_from_days(days);
+
+    let mut result = format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        secs_of_day / 3600,
+        (secs_of_day / 60) % 60,
+        secs_of_day % 60
+    );
+
+    if nanos > 0 {
+        let fraction = format!("{nanos:09}");
+        result.push('.');
+        result.push_str(fraction.trim_end_matches('0'));
+    }
+
+    result.push('Z');
+    result
+}
+
+/// Convert days since 1970-01-01 into a (year, month, day) triple
+///
+/// Based on Howard Hinnant's `civil_from_days` algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::format_rfc3339;
+
+    #[test]
+    fn rfc3339() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        assert_eq!(format_rfc3339(time), "2023-11-14T22:13:20.5Z");
+
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(format_rfc3339(time), "2000-02-29T00:00:00Z");
+
+        let time = UNIX_EPOCH - Duration::from_nanos(1);
+        assert_eq!(format_rfc3339(time), "1969-12-31T23:59:59.999999999Z");
+    }
+}
@@ -0,0 +1,142 @@
+/// Imperfect local clocks, e.g., for simulating clock drift between nodes
+use crate::time::{Duration, Time, START_TIME};
+
+/// A local clock that deviates from the global simulated time
+///
+/// The local time is computed from the global time using a fixed offset,
+/// a drift rate (in parts per million), and a set of jumps at specific points in global time.
+///
+/// Durations (e.g., for sleeping) are only affected by drift, not by jumps,
+/// similar to a monotonic clock in a real system.
+#[derive(Debug, Clone, Default)]
+pub struct LocalClock {
+    offset_nanos: i64,
+    drift_ppm: f64,
+    /// Sorted by time
+    jumps: Vec<(Time, i64)>,
+}
+
+impl LocalClock {
+    /// Create a new local clock
+    ///
+    /// * offset_nanos: How far ahead (or, if negative, behind) the clock is at the start of the simulation
+    /// * drift_ppm: How much faster (or, if negative, slower) the clock runs in parts per million
+    pub fn new(offset_nanos: i64, drift_ppm: f64) -> Self {
+        assert!(
+            drift_ppm > -1_000_000.0,
+            "Clock drift must not stop or reverse time"
+        );
+
+        Self {
+            offset_nanos,
+            drift_ppm,
+            jumps: vec![],
+        }
+    }
+
+    /// Move the clock forward by `amount` when the global time reaches `at`
+    pub fn jump_forward(&mut self, at: Time, amount: Duration) {
+        let amount = i64::try_from(amount.as_nanos()).expect("Clock jump is too large");
+        self.add_jump(at, amount);
+    }
+
+    /// Move the clock backward by `amount` when the global time reaches `at`
+    pub fn jump_backward(&mut self, at: Time, amount: Duration) {
+        let amount = i64::try_from(amount.as_nanos()).expect("Clock jump is too large");
+        self.add_jump(at, -amount);
+    }
+
+    fn add_jump(&mut self, at: Time, amount: i64) {
+        let pos = self.jumps.partition_point(|(time, _)| *time <= at);
+        self.jumps.insert(pos, (at, amount));
+    }
+
+    pub fn get_drift_ppm(&self) -> f64 {
+        self.drift_ppm
+    }
+
+    /// Get the local time corresponding to the given global time
+    ///
+    /// Local time cannot go below the start of the simulation
+    pub fn local_time(&self, global: Time) -> Time {
+        let elapsed = global - START_TIME;
+        let mut nanos =
+            elapsed.as_nanos() as i128 + self.get_drift_nanos(elapsed) + self.offset_nanos as i128;
+
+        for (_, amount) in self.jumps.iter().take_while(|(time, _)| *time <= global) {
+            nanos += *amount as i128;
+        }
+
+        let nanos = nanos.clamp(0, u64::MAX as i128) as u64;
+        Time::from_nanos(nanos)
+    }
+
+    /// How much global time passes while the local clock advances by `local`
+    pub fn to_global_duration(&self, local: Duration) -> Duration {
+        local * (1.0 / self.get_rate())
+    }
+
+    /// How much the local clock advances while `global` time passes
+    pub fn to_local_duration(&self, global: Duration) -> Duration {
+        let nanos = global.as_nanos() as i128 + self.get_drift_nanos(global);
+        Duration::from_nanos(nanos.clamp(0, u64::MAX as i128) as u64)
+    }
+
+    fn get_rate(&self) -> f64 {
+        1.0 + self.drift_ppm / 1_000_000.0
+    }
+
+    /// The drift accumulated over `elapsed` (in nanoseconds)
+    fn get_drift_nanos(&self, elapsed: Duration) -> i128 {
+        (elapsed.as_nanos() as f64 * self.drift_ppm / 1_000_000.0).round() as i128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalClock;
+    use crate::time::{Duration, Time};
+
+    #[test]
+    fn offset_and_drift() {
+        // 100 ppm fast and one millisecond ahead
+        let clock = LocalClock::new(1_000_000, 100.0);
+
+        assert_eq!(clock.local_time(Time::from_nanos(0)), Time::from_millis(1));
+        assert_eq!(
+            clock.local_time(Time::from_seconds(10)),
+            Time::from_millis(10_002)
+        );
+        assert_eq!(
+            clock.to_local_duration(Duration::from_seconds(10)),
+            Duration::from_millis(10_001)
+        );
+        assert_eq!(
+            clock.to_global_duration(Duration::from_millis(10_001)),
+            Duration::from_seconds(10)
+        );
+    }
+
+    #[test]
+    fn jumps() {
+        let mut clock = LocalClock::new(-500, 0.0);
+        clock.jump_forward(Time::from_seconds(1), Duration::from_millis(5));
+        clock.jump_backward(Time::from_seconds(2), Duration::from_millis(10));
+
+        assert_eq!(clock.local_time(Time::from_nanos(100)), Time::from_nanos(0));
+        assert_eq!(
+            clock.local_time(Time::from_seconds(1)),
+            Time::from_nanos(1_004_999_500)
+        );
+        assert_eq!(
+            clock.local_time(Time::from_seconds(2)),
+            Time::from_nanos(1_994_999_500)
+        );
+
+        // Jumps do not affect durations
+        assert_eq!(
+            clock.to_local_duration(Duration::from_seconds(3)),
+            Duration::from_seconds(3)
+        );
+    }
+}
@@ -5,6 +5,13 @@ pub use timer::{SleepFut, Timer};
 mod primitives;
 pub use primitives::{Duration, Time, START_TIME};
 
+mod calendar;
+
+mod clock;
+pub use clock::LocalClock;
+
+use std::time::SystemTime;
+
 /// Make this task wait for the specified duration
 pub fn sleep(duration: Duration) -> SleepFut {
     crate::CONTEXT.with(|hdl| {
@@ -26,3 +33,16 @@ pub fn now() -> Time {
             .now()
     })
 }
+
+/// Get the wall-clock time at which the simulation started
+///
+/// Returns None if the runtime was not created with an epoch
+pub fn epoch() -> Option<SystemTime> {
+    crate::CONTEXT.with(|hdl| {
+        hdl.borrow()
+            .as_ref()
+            .expect("Not in an asim context")
+            .get_timer()
+            .get_epoch()
+    })
+}
@@ -1,18 +1,30 @@
+use std::str::FromStr;
+use std::time::SystemTime;
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::parse::{parse_scaled, split_quantity, ParseError};
+
 /// Elapsed simulated time in nanoseconds
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Time(u64);
 
-/// A period of simulated time
+/// A period of simulated time in nanoseconds
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Duration(u64);
 
 impl Time {
+    /// The latest point in time that can be represented
+    pub const MAX: Self = Self(u64::MAX);
+
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(nanos)
+    }
+
     pub const fn from_micros(micros: u64) -> Self {
-        Self(micros)
+        Self::from_nanos(micros * 1000)
     }
 
     pub const fn from_millis(millis: u64) -> Self {
@@ -43,31 +55,110 @@ impl Time {
 
     /// Get elapsed seconds (rounded down)
     pub fn to_seconds(&self) -> u64 {
-        self.0 / 1_000_000
+        self.0 / 1_000_000_000
     }
 
     pub fn to_millis(&self) -> u64 {
-        self.0 / 1_000
+        self.0 / 1_000_000
     }
 
+    /// Get elapsed microseconds (rounded down)
     pub fn as_micros(&self) -> u64 {
+        self.0 / 1_000
+    }
+
+    pub fn as_nanos(&self) -> u64 {
         self.0
     }
 
     pub fn as_millis_f64(&self) -> f64 {
-        (self.0 as f64) / 1_000.0
+        (self.0 as f64) / 1_000_000.0
     }
 
     pub fn as_seconds_f64(&self) -> f64 {
-        (self.0 as f64) / (1_000_000.0)
+        (self.0 as f64) / 1_000_000_000.0
+    }
+
+    /// Returns `None` if the result would overflow
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(duration.0).map(Self)
+    }
+
+    /// Returns `None` if the result would be before the start of the simulation
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(duration.0).map(Self)
+    }
+
+    pub fn saturating_add(self, duration: Duration) -> Self {
+        Self(self.0.saturating_add(duration.0))
+    }
+
+    pub fn saturating_sub(self, duration: Duration) -> Self {
+        Self(self.0.saturating_sub(duration.0))
+    }
+
+    /// Time elapsed since `earlier`, or `None` if `earlier` is later than this time
+    pub fn checked_duration_since(self, earlier: Self) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(Duration)
+    }
+
+    /// Time elapsed since `earlier`, or zero if `earlier` is later than this time
+    pub fn saturating_duration_since(self, earlier: Self) -> Duration {
+        Duration(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Convert this into wall-clock time using the given epoch
+    pub fn to_system_time_with_epoch(self, epoch: SystemTime) -> SystemTime {
+        epoch + std::time::Duration::from_nanos(self.0)
+    }
+
+    /// Map a wall-clock time to simulated time using the given epoch
+    ///
+    /// Returns `None` if the time is before the epoch or too far after it
+    pub fn from_system_time_with_epoch(time: SystemTime, epoch: SystemTime) -> Option<Self> {
+        let elapsed = time.duration_since(epoch).ok()?;
+        Duration::try_from(elapsed).ok().map(|d| START_TIME + d)
+    }
+
+    /// Convert this into wall-clock time using the current runtime's epoch
+    ///
+    /// Note, this will panic if the runtime has no epoch
+    pub fn to_system_time(self) -> SystemTime {
+        self.to_system_time_with_epoch(Self::current_epoch())
+    }
+
+    /// Map a wall-clock time to simulated time using the current runtime's epoch
+    ///
+    /// Returns `None` if the time is before the epoch or too far after it.
+    /// Note, this will panic if the runtime has no epoch
+    pub fn from_system_time(time: SystemTime) -> Option<Self> {
+        Self::from_system_time_with_epoch(time, Self::current_epoch())
+    }
+
+    /// Format this as an RFC 3339 date (in UTC) using the current runtime's epoch
+    ///
+    /// Note, this will panic if the runtime has no epoch
+    pub fn to_rfc3339(self) -> String {
+        super::calendar::format_rfc3339(self.to_system_time())
+    }
+
+    fn current_epoch() -> SystemTime {
+        super::epoch().expect("Runtime has no epoch set")
     }
 }
 
 impl Duration {
     pub const ZERO: Self = Self(0);
 
+    /// The longest duration that can be represented
+    pub const MAX: Self = Self(u64::MAX);
+
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(nanos)
+    }
+
     pub const fn from_micros(micros: u64) -> Self {
-        Self(micros)
+        Self::from_nanos(micros * 1000)
     }
 
     pub const fn from_millis(millis: u64) -> Self {
@@ -106,23 +197,60 @@ impl Duration {
 
     /// Get duration in seconds (rounded down)
     pub fn to_seconds(self) -> u64 {
-        self.0 / (1_000_000)
+        self.0 / 1_000_000_000
     }
 
     pub fn to_millis(self) -> u64 {
-        self.0 / 1_000
+        self.0 / 1_000_000
     }
 
+    /// Get duration in microseconds (rounded down)
     pub fn as_micros(&self) -> u64 {
+        self.0 / 1_000
+    }
+
+    pub fn as_nanos(&self) -> u64 {
         self.0
     }
 
     pub fn as_millis_f64(&self) -> f64 {
-        (self.0 as f64) / 1_000.0
+        (self.0 as f64) / 1_000_000.0
     }
 
     pub fn as_seconds_f64(&self) -> f64 {
-        (self.0 as f64) / (1_000_000.0)
+        (self.0 as f64) / 1_000_000_000.0
+    }
+
+    /// Returns `None` if the result would overflow
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Returns `None` if `other` is longer than this duration
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Returns `None` if the result would overflow
+    pub fn checked_mul(self, factor: u64) -> Option<Self> {
+        self.0.checked_mul(factor).map(Self)
+    }
+
+    /// Returns `None` if `divisor` is zero
+    pub fn checked_div(self, divisor: u64) -> Option<Self> {
+        self.0.checked_div(divisor).map(Self)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    pub fn saturating_mul(self, factor: u64) -> Self {
+        Self(self.0.saturating_mul(factor))
     }
 }
 
@@ -130,13 +258,26 @@ impl std::ops::Add<Duration> for Time {
     type Output = Self;
 
     fn add(self, other: Duration) -> Self {
-        Self(self.0 + other.0)
+        self.checked_add(other)
+            .expect("overflow when adding duration to time")
+    }
+}
+
+impl std::ops::AddAssign<Duration> for Time {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
     }
 }
 
 impl std::ops::AddAssign<Duration> for Duration {
     fn add_assign(&mut self, other: Duration) {
-        self.0 += other.0
+        *self = *self + other;
+    }
+}
+
+impl std::ops::SubAssign<Duration> for Duration {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
     }
 }
 
@@ -144,7 +285,8 @@ impl std::ops::Add for Duration {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        Self(self.0 + other.0)
+        self.checked_add(other)
+            .expect("overflow when adding durations")
     }
 }
 
@@ -152,7 +294,46 @@ impl std::ops::Sub for Duration {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
-        Self(self.0 - other.0)
+        self.checked_sub(other)
+            .expect("overflow when subtracting durations")
+    }
+}
+
+impl std::ops::Mul<u64> for Duration {
+    type Output = Self;
+
+    fn mul(self, factor: u64) -> Self {
+        self.checked_mul(factor)
+            .expect("overflow when multiplying duration")
+    }
+}
+
+impl std::ops::Mul<f64> for Duration {
+    type Output = Self;
+
+    /// Panics if the factor is negative, not finite, or the result would overflow
+    fn mul(self, factor: f64) -> Self {
+        assert!(
+            factor.is_finite() && factor >= 0.0,
+            "invalid factor for duration: {factor}"
+        );
+
+        let nanos = (self.0 as f64 * factor).round();
+        assert!(
+            nanos < u64::MAX as f64,
+            "overflow when multiplying duration"
+        );
+
+        Self(nanos as u64)
+    }
+}
+
+impl std::ops::Div<u64> for Duration {
+    type Output = Self;
+
+    fn div(self, divisor: u64) -> Self {
+        self.checked_div(divisor)
+            .expect("divide by zero error when dividing duration")
     }
 }
 
@@ -160,7 +341,8 @@ impl std::ops::Sub<Self> for Time {
     type Output = Duration;
 
     fn sub(self, other: Self) -> Duration {
-        Duration(self.0 - other.0)
+        self.checked_duration_since(other)
+            .expect("overflow when subtracting times")
     }
 }
 
@@ -168,13 +350,100 @@ impl std::ops::Sub<Duration> for Time {
     type Output = Self;
 
     fn sub(self, other: Duration) -> Self {
-        Self(self.0 - other.0)
+        self.checked_sub(other)
+            .expect("overflow when subtracting duration from time")
     }
 }
 
+impl From<Duration> for std::time::Duration {
+    fn from(duration: Duration) -> Self {
+        std::time::Duration::from_nanos(duration.0)
+    }
+}
+
+impl TryFrom<std::time::Duration> for Duration {
+    type Error = std::num::TryFromIntError;
+
+    /// Fails if the duration is too long to be represented
+    fn try_from(duration: std::time::Duration) -> Result<Self, Self::Error> {
+        u64::try_from(duration.as_nanos()).map(Self)
+    }
+}
+
+/// Units that can be used when writing durations, with their length in nanoseconds
+///
+/// Sorted from largest to smallest
+const DURATION_UNITS: [(&str, u64); 7] = [
+    ("d", 24 * 60 * 60 * 1_000_000_000),
+    ("h", 60 * 60 * 1_000_000_000),
+    ("min", 60 * 1_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("μs", 1_000),
+    ("ns", 1),
+];
+
+/// Writes the duration using the largest unit that represents it exactly
+/// (e.g., "150ms" or "2s")
 impl std::fmt::Display for Duration {
     fn fmt(&self, w: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(w, "{}μs", self.0)
+        if self.is_zero() {
+            return write!(w, "0s");
+        }
+
+        let (unit, scale) = DURATION_UNITS
+            .iter()
+            .find(|(_, scale)| self.0.is_multiple_of(*scale))
+            .expect("Nanoseconds always divide the duration");
+
+        write!(w, "{}{unit}", self.0 / scale)
+    }
+}
+
+/// Parses durations such as "150ms", "2.5s" or "1h"
+///
+/// Supported units are "d", "h", "min", "s", "ms", "us" (or "μs"), and "ns"
+impl FromStr for Duration {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, ParseError> {
+        let (number, unit) = split_quantity(input)?;
+        let unit = if unit == "us" { "μs" } else { unit };
+
+        let Some((_, scale)) = DURATION_UNITS.iter().find(|(name, _)| *name == unit) else {
+            return Err(ParseError::new(format!(
+                "Invalid or missing time unit in \"{input}\""
+            )));
+        };
+
+        parse_scaled(number, *scale).map(Self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl crate::parse::Quantity for Duration {
+    const EXPECTING: &'static str = "a duration in nanoseconds or a string like \"150ms\"";
+
+    fn from_raw(nanos: u64) -> Self {
+        Self(nanos)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Duration {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::parse::serialize_quantity(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::parse::deserialize_quantity(deserializer)
     }
 }
 
@@ -210,6 +479,82 @@ mod tests {
         assert_eq!(2, duration.to_seconds());
         assert_eq!(2_000, duration.to_millis());
         assert_eq!(2_000_000, duration.as_micros());
+        assert_eq!(2_000_000_000, duration.as_nanos());
+    }
+
+    #[test]
+    fn duration_arithmetic() {
+        let duration = Duration::from_nanos(1_500);
+
+        assert_eq!(duration * 2, Duration::from_micros(3));
+        assert_eq!(duration / 3, Duration::from_nanos(500));
+        assert_eq!(duration * 0.5, Duration::from_nanos(750));
+
+        assert_eq!(duration.checked_sub(Duration::from_micros(2)), None);
+        assert_eq!(
+            duration.saturating_sub(Duration::from_micros(2)),
+            Duration::ZERO
+        );
+        assert_eq!(Duration::MAX.checked_add(duration), None);
+        assert_eq!(Duration::MAX.saturating_mul(2), Duration::MAX);
+        assert_eq!(duration.checked_div(0), None);
+    }
+
+    #[test]
+    fn std_duration_conversion() {
+        let duration = Duration::from_millis(1_234);
+        let std_duration: std::time::Duration = duration.into();
+
+        assert_eq!(std_duration, std::time::Duration::from_millis(1_234));
+        assert_eq!(Duration::try_from(std_duration), Ok(duration));
+        assert!(Duration::try_from(std::time::Duration::MAX).is_err());
+    }
+
+    #[test]
+    fn duration_display() {
+        assert_eq!(Duration::from_millis(150).to_string(), "150ms");
+        assert_eq!(Duration::from_millis(1_500).to_string(), "1500ms");
+        assert_eq!(Duration::from_seconds(120).to_string(), "2min");
+        assert_eq!(Duration::from_nanos(1_001).to_string(), "1001ns");
+        assert_eq!(Duration::ZERO.to_string(), "0s");
+    }
+
+    #[test]
+    fn duration_from_str() {
+        assert_eq!("150ms".parse(), Ok(Duration::from_millis(150)));
+        assert_eq!("2.5s".parse(), Ok(Duration::from_millis(2_500)));
+        assert_eq!("3 us".parse(), Ok(Duration::from_micros(3)));
+        assert_eq!("1d".parse(), Ok(Duration::from_days(1)));
+        assert!("1.5ns".parse::<Duration>().is_err());
+        assert!("10".parse::<Duration>().is_err());
+        assert!("10 parsecs".parse::<Duration>().is_err());
+
+        for duration in [Duration::from_nanos(1_001), Duration::from_hours(25)] {
+            assert_eq!(duration.to_string().parse(), Ok(duration));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn duration_serde() {
+        let duration = Duration::from_millis(150);
+        assert_eq!(serde_json::to_string(&duration).unwrap(), "\"150ms\"");
+
+        let parsed: Duration = serde_json::from_str("\"2.5s\"").unwrap();
+        assert_eq!(parsed, Duration::from_millis(2_500));
+
+        let parsed: Duration = serde_json::from_str("1000").unwrap();
+        assert_eq!(parsed, Duration::from_micros(1));
+    }
+
+    #[test]
+    fn time_difference() {
+        let earlier = Time::from_millis(5);
+        let later = Time::from_millis(7);
+
+        assert_eq!(later - earlier, Duration::from_millis(2));
+        assert_eq!(earlier.checked_duration_since(later), None);
+        assert_eq!(earlier.saturating_duration_since(later), Duration::ZERO);
     }
 
     #[test]
@@ -6,17 +6,20 @@ use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::task::{Context, Poll, Waker};
+use std::time::SystemTime;
 
 use crate::time::{Duration, Time};
 
 struct TimeEvent {
     wake_time: Time,
+    /// Breaks ties, so that events for the same time fire in the order they were scheduled
+    sequence: u64,
     waker: Waker,
 }
 
 impl PartialEq for TimeEvent {
     fn eq(&self, other: &Self) -> bool {
-        self.wake_time.eq(&other.wake_time)
+        (self.wake_time, self.sequence).eq(&(other.wake_time, other.sequence))
     }
 }
 
@@ -30,43 +33,65 @@ impl Eq for TimeEvent {}
 
 impl Ord for TimeEvent {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.wake_time.cmp(&other.wake_time)
+        (self.wake_time, self.sequence).cmp(&(other.wake_time, other.sequence))
     }
 }
 
 pub struct Timer {
     current_time: Rc<AtomicU64>,
     time_events: Rc<RefCell<BinaryHeap<Reverse<TimeEvent>>>>,
+    next_sequence: Rc<AtomicU64>,
+    epoch: Option<SystemTime>,
 }
 
 impl Timer {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(epoch: Option<SystemTime>) -> Self {
         Self {
             current_time: Default::default(),
             time_events: Default::default(),
+            next_sequence: Default::default(),
+            epoch,
         }
     }
 
-    /// Current simulation time (in milliseconds)
+    /// The wall-clock time the start of the simulation corresponds to (if any)
+    pub fn get_epoch(&self) -> Option<SystemTime> {
+        self.epoch
+    }
+
+    /// Current simulation time
     pub fn now(&self) -> Time {
-        let micros = self.current_time.load(Ordering::SeqCst);
-        Time::from_micros(micros)
+        let nanos = self.current_time.load(Ordering::SeqCst);
+        Time::from_nanos(nanos)
     }
 
     /// Advance time to the next event and schedule it to be run
     ///
+    /// All other events scheduled for the same time are woken up as well,
+    /// so that their tasks are ordered by priority together.
+    ///
     /// Return true if any time event existed
     pub fn advance(&self) -> bool {
         let mut time_events = self.time_events.borrow_mut();
-        if let Some(Reverse(time_event)) = time_events.pop() {
-            // Move to the time of the next event
-            self.current_time
-                .store(time_event.wake_time.as_micros(), Ordering::SeqCst);
+        let Some(Reverse(time_event)) = time_events.pop() else {
+            return false;
+        };
+
+        // Move to the time of the next event
+        let wake_time = time_event.wake_time;
+        self.current_time
+            .store(wake_time.as_nanos(), Ordering::SeqCst);
+        time_event.waker.wake();
+
+        while time_events
+            .peek()
+            .is_some_and(|Reverse(event)| event.wake_time == wake_time)
+        {
+            let Reverse(time_event) = time_events.pop().unwrap();
             time_event.waker.wake();
-            true
-        } else {
-            false
         }
+
+        true
     }
 
     /// Make this task wait for the specified duration
@@ -83,6 +108,7 @@ impl Timer {
         SleepFut {
             current_time: self.current_time.clone(),
             time_events: self.time_events.clone(),
+            next_sequence: self.next_sequence.clone(),
             wake_time,
         }
     }
@@ -91,6 +117,7 @@ impl Timer {
 pub struct SleepFut {
     current_time: Rc<AtomicU64>,
     time_events: Rc<RefCell<BinaryHeap<Reverse<TimeEvent>>>>,
+    next_sequence: Rc<AtomicU64>,
     wake_time: Time,
 }
 
@@ -99,8 +126,8 @@ impl Future for SleepFut {
 
     fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
         let now = {
-            let micros = self.current_time.load(Ordering::SeqCst);
-            Time::from_micros(micros)
+            let nanos = self.current_time.load(Ordering::SeqCst);
+            Time::from_nanos(nanos)
         };
 
         if now >= self.wake_time {
@@ -109,6 +136,7 @@ impl Future for SleepFut {
             let mut time_events = self.time_events.borrow_mut();
             time_events.push(Reverse(TimeEvent {
                 wake_time: self.wake_time,
+                sequence: self.next_sequence.fetch_add(1, Ordering::SeqCst),
                 waker: ctx.waker().clone(),
             }));
 
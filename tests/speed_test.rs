@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use asim::network::{Bandwidth, Latency, NetworkMessage, ObjectId};
+use asim::network::{Bandwidth, Latency, NetworkMessage, NodeId};
 use asim::sync::oneshot;
 use asim::time::Duration;
 
@@ -26,7 +26,7 @@ impl NetworkMessage for Message {
 
 #[async_trait::async_trait(?Send)]
 impl asim::network::NodeCallback<Message, NodeData> for NodeCallback {
-    async fn handle_message(&self, node: &Rc<Node>, _source: ObjectId, _message: Message) {
+    async fn handle_message(&self, node: &Rc<Node>, _source: NodeId, _message: Message) {
         node.get_data()
             .notifier
             .borrow_mut()